@@ -2,8 +2,38 @@ use anyhow::{Context, Result};
 use aws_config::meta::region::RegionProviderChain;
 use aws_config::profile::ProfileFileCredentialsProvider;
 use aws_config::{BehaviorVersion, Region};
+use aws_sdk_ssm::primitives::DateTime;
+use aws_sdk_ssm::types::{ParameterStringFilter, ParameterType};
 use aws_sdk_ssm::Client;
 use log::debug;
+use std::collections::HashMap;
+
+/// A parameter's value along with what Parameter Store knows about it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub value: String,
+    pub parameter_type: Option<ParameterType>,
+    pub version: i64,
+    pub last_modified: Option<DateTime>,
+    pub arn: Option<String>,
+}
+
+impl Parameter {
+    /// SecureStrings are secrets by definition, whatever their key is called.
+    pub fn is_secure(&self) -> bool {
+        self.parameter_type == Some(ParameterType::SecureString)
+    }
+}
+
+/// A parameter found directly under a prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListedParameter {
+    pub name: String,
+    /// The plaintext value; always `None` for SecureStrings, which are listed without decryption.
+    pub value: Option<String>,
+    pub secure: bool,
+    pub description: Option<String>,
+}
 
 pub struct AwsClient {
     ssm_client: Client,
@@ -36,7 +66,7 @@ impl AwsClient {
         Ok(AwsClient { ssm_client })
     }
 
-    pub async fn get_parameter(&self, name: &str) -> Result<Option<String>> {
+    pub async fn get_parameter(&self, name: &str) -> Result<Option<Parameter>> {
         debug!("Getting parameter: {}", name);
 
         match self
@@ -51,7 +81,13 @@ impl AwsClient {
                 if let Some(parameter) = result.parameter {
                     if let Some(value) = parameter.value {
                         debug!("Successfully retrieved parameter: {}", name);
-                        Ok(Some(value))
+                        Ok(Some(Parameter {
+                            value,
+                            parameter_type: parameter.r#type,
+                            version: parameter.version,
+                            last_modified: parameter.last_modified_date,
+                            arn: parameter.arn,
+                        }))
                     } else {
                         debug!("Parameter {} has no value", name);
                         Ok(None)
@@ -76,4 +112,150 @@ impl AwsClient {
             }
         }
     }
+
+    /// Lists the parameters directly under `prefix` (not recursively), sorted by name.
+    pub async fn list_parameters(&self, prefix: &str) -> Result<Vec<ListedParameter>> {
+        let mut parameters = self.describe_parameters(prefix).await?;
+
+        // Values come from GetParametersByPath; SecureStrings stay encrypted and are ignored
+        let mut values = HashMap::new();
+        let mut next_token = None;
+
+        loop {
+            let result = self
+                .ssm_client
+                .get_parameters_by_path()
+                .path(prefix_path(prefix))
+                .recursive(false)
+                .with_decryption(false)
+                .set_next_token(next_token)
+                .send()
+                .await
+                .map_err(|err| anyhow::anyhow!("AWS SSM error: {}", err.into_service_error()))
+                .with_context(|| format!("Failed to get parameters under: {}", prefix))?;
+
+            for parameter in result.parameters() {
+                if let (Some(name), Some(value)) = (parameter.name(), parameter.value()) {
+                    values.insert(name.to_string(), value.to_string());
+                }
+            }
+
+            next_token = result.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+
+        for parameter in parameters.iter_mut().filter(|p| !p.secure) {
+            parameter.value = values.remove(&parameter.name);
+        }
+
+        Ok(parameters)
+    }
+
+    /// Like [`AwsClient::list_parameters`], but without values, which saves a
+    /// round of requests when only names and types are needed.
+    pub async fn describe_parameters(&self, prefix: &str) -> Result<Vec<ListedParameter>> {
+        debug!("Listing parameters under: {}", prefix);
+
+        let filter = ParameterStringFilter::builder()
+            .key("Path")
+            .option("OneLevel")
+            .values(prefix_path(prefix))
+            .build()?;
+        let parameters = self.describe(filter)
+            .await
+            .with_context(|| format!("Failed to list parameters under: {}", prefix))?;
+        debug!("Found {} parameters under {}", parameters.len(), prefix);
+
+        Ok(parameters)
+    }
+
+    /// Whether the parameter `name` exists, without fetching or decrypting its value.
+    pub async fn parameter_exists(&self, name: &str) -> Result<bool> {
+        let filter = ParameterStringFilter::builder()
+            .key("Name")
+            .option("Equals")
+            .values(name)
+            .build()?;
+        let parameters = self.describe(filter)
+            .await
+            .with_context(|| format!("Failed to look up parameter: {}", name))?;
+
+        Ok(!parameters.is_empty())
+    }
+
+    /// Descriptions and types of the parameters matching `filter`, from DescribeParameters.
+    async fn describe(&self, filter: ParameterStringFilter) -> Result<Vec<ListedParameter>> {
+        let mut parameters = Vec::new();
+        let mut next_token = None;
+
+        loop {
+            let result = self
+                .ssm_client
+                .describe_parameters()
+                .parameter_filters(filter.clone())
+                .set_next_token(next_token)
+                .send()
+                .await
+                .map_err(|err| anyhow::anyhow!("AWS SSM error: {}", err.into_service_error()))?;
+
+            for metadata in result.parameters() {
+                if let Some(name) = metadata.name() {
+                    parameters.push(ListedParameter {
+                        name: name.to_string(),
+                        value: None,
+                        secure: metadata.r#type() == Some(&ParameterType::SecureString),
+                        description: metadata.description().map(str::to_string),
+                    });
+                }
+            }
+
+            next_token = result.next_token;
+            if next_token.is_none() {
+                break;
+            }
+        }
+
+        parameters.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(parameters)
+    }
+}
+
+/// `prefix` without its trailing slash, as the listing APIs expect.
+fn prefix_path(prefix: &str) -> &str {
+    match prefix.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_path() {
+        assert_eq!(prefix_path("/studio-dev/"), "/studio-dev");
+        assert_eq!(prefix_path("/studio-dev"), "/studio-dev");
+        assert_eq!(prefix_path("/"), "/");
+    }
+
+    #[test]
+    fn test_parameter_is_secure() {
+        let mut parameter = Parameter {
+            value: "hunter2".to_string(),
+            parameter_type: Some(ParameterType::SecureString),
+            version: 3,
+            last_modified: None,
+            arn: None,
+        };
+        assert!(parameter.is_secure());
+
+        parameter.parameter_type = Some(ParameterType::String);
+        assert!(!parameter.is_secure());
+
+        parameter.parameter_type = None;
+        assert!(!parameter.is_secure());
+    }
 }
\ No newline at end of file
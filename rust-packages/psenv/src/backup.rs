@@ -0,0 +1,287 @@
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::lock::OutputLock;
+use crate::output_file::{write_atomic, FileOptions};
+
+/// Versions a run replaced, restored by `rollback`.
+const BACKUP: &str = "bak";
+/// Versions a rollback replaced, restored by `rollback --undo`.
+const UNDO: &str = "undo";
+
+/// Saves `content`, the current contents of `path`, as `<path>.bak.<timestamp>`
+/// and deletes all but the newest `retention` backups.
+pub fn create_backup(path: &Path, content: &str, retention: usize, options: &FileOptions) -> Result<PathBuf> {
+    save_copy(path, BACKUP, content, retention, options)
+}
+
+/// Backups of `path`, oldest first.
+#[cfg(test)]
+pub fn list_backups(path: &Path) -> Result<Vec<PathBuf>> {
+    list_copies(path, BACKUP)
+}
+
+/// Restores the newest backup of `path` and removes it, so repeated rollbacks
+/// go further back. The replaced file is kept as `<path>.undo.<timestamp>`
+/// for [`undo_rollback`]. Returns the backup that was restored.
+pub fn rollback(path: &Path, retention: usize, options: &FileOptions, lock_timeout: Duration) -> Result<PathBuf> {
+    restore(path, BACKUP, UNDO, retention, options, lock_timeout)
+}
+
+/// Reverses the last [`rollback`], saving the replaced file as a backup again.
+pub fn undo_rollback(path: &Path, retention: usize, options: &FileOptions, lock_timeout: Duration) -> Result<PathBuf> {
+    restore(path, UNDO, BACKUP, retention, options, lock_timeout)
+}
+
+/// Replaces `path` with its newest `from` copy, which is removed, after saving
+/// the current contents as a `to` copy.
+fn restore(
+    path: &Path,
+    from: &str,
+    to: &str,
+    retention: usize,
+    options: &FileOptions,
+    lock_timeout: Duration,
+) -> Result<PathBuf> {
+    let no_copies = || match from {
+        UNDO => anyhow!("No rollback of {} to undo", path.display()),
+        _ => anyhow!("No backups found for {}", path.display()),
+    };
+
+    // Don't leave a lock file behind when there is nothing to restore
+    if list_copies(path, from)?.is_empty() {
+        return Err(no_copies());
+    }
+
+    let _lock = OutputLock::acquire(path, lock_timeout, options)?;
+
+    // Another run may have restored it in the meantime
+    let latest = list_copies(path, from)?.pop().ok_or_else(no_copies)?;
+
+    let content = fs::read_to_string(&latest)
+        .with_context(|| format!("Failed to read {}", latest.display()))?;
+
+    match fs::read_to_string(path) {
+        // Never lose the current version, even with backups turned off
+        Ok(current) => {
+            save_copy(path, to, &current, retention.max(1), options)?;
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+
+    write_atomic(path, &content, options)
+        .with_context(|| format!("Failed to restore {}", path.display()))?;
+    fs::remove_file(&latest)
+        .with_context(|| format!("Failed to remove restored copy: {}", latest.display()))?;
+
+    Ok(latest)
+}
+
+/// Writes `content` to `<path>.<kind>.<timestamp>` and keeps only the newest
+/// `retention` copies of that kind.
+fn save_copy(path: &Path, kind: &str, content: &str, retention: usize, options: &FileOptions) -> Result<PathBuf> {
+    let mut copy = copy_path(path, kind, &timestamp(SystemTime::now()));
+    // Two runs within the same millisecond
+    let mut attempt = 1;
+    while copy.exists() {
+        copy = copy_path(path, kind, &format!("{}-{}", timestamp(SystemTime::now()), attempt));
+        attempt += 1;
+    }
+
+    // Copies are new files, so they get the restrictive default mode rather than --mode
+    let options = FileOptions { mode: None, ..options.clone() };
+    write_atomic(&copy, content, &options)
+        .with_context(|| format!("Failed to write backup: {}", copy.display()))?;
+
+    debug!("Backed up {} to {}", path.display(), copy.display());
+
+    let copies = list_copies(path, kind)?;
+    if copies.len() > retention {
+        for old in &copies[..copies.len() - retention] {
+            debug!("Removing old backup: {}", old.display());
+            fs::remove_file(old)
+                .with_context(|| format!("Failed to remove old backup: {}", old.display()))?;
+        }
+    }
+
+    Ok(copy)
+}
+
+/// Copies of `path` of the given kind, oldest first.
+fn list_copies(path: &Path, kind: &str) -> Result<Vec<PathBuf>> {
+    let file_name = path.file_name()
+        .with_context(|| format!("Not a file path: {}", path.display()))?
+        .to_string_lossy();
+    let prefix = format!("{}.{}.", file_name, kind);
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read directory: {}", dir.display())),
+    };
+
+    let mut copies: Vec<(String, PathBuf)> = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        // Hand-made copies like `.env.bak.old` are not ours to delete
+        if let Some(stamp) = name.strip_prefix(&prefix).filter(|stamp| is_timestamp(stamp)) {
+            copies.push((stamp.to_string(), entry.path()));
+        }
+    }
+
+    copies.sort();
+    Ok(copies.into_iter().map(|(_, path)| path).collect())
+}
+
+fn copy_path(path: &Path, kind: &str, stamp: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}.{}", kind, stamp));
+    PathBuf::from(name)
+}
+
+/// UTC time as `20261018T153000.123Z`, which sorts chronologically.
+fn timestamp(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let seconds_of_day = secs % 86_400;
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+fn is_timestamp(stamp: &str) -> bool {
+    let base = stamp.split_once('-').map_or(stamp, |(base, _)| base);
+    let bytes = base.as_bytes();
+
+    bytes.len() == 20
+        && bytes.iter().enumerate().all(|(i, b)| match i {
+            8 => *b == b'T',
+            15 => *b == b'.',
+            19 => *b == b'Z',
+            _ => b.is_ascii_digit(),
+        })
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_792_337_400_123);
+        assert_eq!(timestamp(time), "20261018T153000.123Z");
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101T000000.000Z");
+
+        assert!(is_timestamp("20261018T153000.123Z"));
+        assert!(is_timestamp("20261018T153000.123Z-2"));
+        assert!(!is_timestamp("old"));
+    }
+
+    #[test]
+    fn test_backup_retention_and_rollback() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        let manual = dir.path().join(".env.bak.old");
+        fs::write(&manual, "MANUAL=1\n").unwrap();
+
+        for version in 1..=4 {
+            create_backup(&path, &format!("V={}\n", version), 3, &FileOptions::default()).unwrap();
+        }
+
+        let backups = list_backups(&path).unwrap();
+        assert_eq!(backups.len(), 3);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), "V=2\n");
+        assert!(manual.exists());
+
+        fs::write(&path, "V=broken\n").unwrap();
+        let restored = rollback(&path, 3, &FileOptions::default(), Duration::ZERO).unwrap();
+        assert_eq!(restored, backups[2]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "V=4\n");
+
+        rollback(&path, 3, &FileOptions::default(), Duration::ZERO).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "V=3\n");
+        assert_eq!(list_backups(&path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_rollbacks_can_be_undone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        let read = || fs::read_to_string(&path).unwrap();
+
+        for version in 1..=2 {
+            create_backup(&path, &format!("V={}\n", version), 5, &FileOptions::default()).unwrap();
+        }
+        fs::write(&path, "V=3\n").unwrap();
+
+        rollback(&path, 5, &FileOptions::default(), Duration::ZERO).unwrap();
+        assert_eq!(read(), "V=2\n");
+        rollback(&path, 5, &FileOptions::default(), Duration::ZERO).unwrap();
+        assert_eq!(read(), "V=1\n");
+        assert!(rollback(&path, 5, &FileOptions::default(), Duration::ZERO).is_err());
+
+        // Every version replaced by a rollback comes back, newest last
+        undo_rollback(&path, 5, &FileOptions::default(), Duration::ZERO).unwrap();
+        assert_eq!(read(), "V=2\n");
+        undo_rollback(&path, 5, &FileOptions::default(), Duration::ZERO).unwrap();
+        assert_eq!(read(), "V=3\n");
+        assert!(undo_rollback(&path, 5, &FileOptions::default(), Duration::ZERO).is_err());
+
+        // ... and can be rolled back once more
+        rollback(&path, 5, &FileOptions::default(), Duration::ZERO).unwrap();
+        assert_eq!(read(), "V=2\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_backup_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let options = FileOptions { mode: Some(0o644), ..FileOptions::default() };
+
+        let backup = create_backup(&dir.path().join(".env"), "A=1\n", 5, &options).unwrap();
+
+        assert_eq!(fs::metadata(backup).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_rollback_without_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(rollback(&dir.path().join(".env"), 5, &FileOptions::default(), Duration::ZERO).is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
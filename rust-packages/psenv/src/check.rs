@@ -0,0 +1,206 @@
+use std::collections::HashSet;
+
+use crate::path_mapping::{candidate_paths, PathMapping, Precedence};
+use crate::template_loader::TemplateEntry;
+
+/// A template key none of whose parameter paths exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingKey {
+    pub key: String,
+    pub paths: Vec<String>,
+    /// Not required, as in a normal run, so reported without failing the check.
+    pub optional: bool,
+}
+
+/// Differences between templates and the parameters in Parameter Store.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CheckReport {
+    pub missing: Vec<MissingKey>,
+    /// Parameters under a prefix that no template key refers to.
+    pub orphans: Vec<String>,
+}
+
+impl CheckReport {
+    /// Compares template keys against `existing` parameter paths.
+    ///
+    /// `listed` holds the parameters found directly under the prefixes; only
+    /// those can be orphans. Keys in `ignore_keys` are never missing, but
+    /// their parameters aren't orphans either. Whether a missing key fails
+    /// the check follows [`TemplateEntry::is_required`].
+    pub fn build(
+        entries: &[TemplateEntry],
+        prefixes: &[String],
+        path_mapping: &PathMapping,
+        existing: &HashSet<String>,
+        listed: &[String],
+        ignore_keys: &[String],
+        require_all: bool,
+    ) -> Self {
+        let mut report = CheckReport::default();
+        let mut referenced = HashSet::new();
+
+        for entry in entries {
+            let paths = expected_paths(entry, prefixes, path_mapping);
+
+            if !ignore_keys.contains(&entry.key) && !paths.iter().any(|path| existing.contains(path)) {
+                report.missing.push(MissingKey {
+                    key: entry.key.clone(),
+                    paths: paths.clone(),
+                    optional: !entry.is_required(require_all),
+                });
+            }
+
+            referenced.extend(paths);
+        }
+
+        report.orphans = listed.iter()
+            .filter(|name| !referenced.contains(*name))
+            .cloned()
+            .collect();
+        report.orphans.sort();
+
+        report
+    }
+
+    pub fn missing_required(&self) -> usize {
+        self.missing.iter().filter(|missing| !missing.optional).count()
+    }
+
+    /// One line per finding, suitable for CI logs.
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        for missing in &self.missing {
+            let label = if missing.optional { "missing (optional)" } else { "missing" };
+            output.push_str(&format!("{}: {} (looked up {})\n", label, missing.key, missing.paths.join(", ")));
+        }
+
+        for orphan in &self.orphans {
+            output.push_str(&format!("orphan: {}\n", orphan));
+        }
+
+        output
+    }
+}
+
+/// The paths a key may be fetched from, in the same way as a normal run.
+pub fn expected_paths(entry: &TemplateEntry, prefixes: &[String], path_mapping: &PathMapping) -> Vec<String> {
+    let path_override = path_mapping.get(&entry.key).or(entry.path.as_deref());
+    candidate_paths(prefixes, Precedence::FirstWins, &entry.key, path_override)
+}
+
+/// Whether listing `prefixes` one level deep would have returned `path`.
+pub fn is_listed(path: &str, prefixes: &[String]) -> bool {
+    let Some((parent, _)) = path.rsplit_once('/') else {
+        return false;
+    };
+
+    prefixes.iter().any(|prefix| prefix.trim_end_matches('/') == parent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, required: Option<bool>, path: Option<&str>) -> TemplateEntry {
+        TemplateEntry {
+            key: key.to_string(),
+            default: None,
+            description: None,
+            source: ".env.example".to_string(),
+            line: 1,
+            column: 1,
+            commented: false,
+            required,
+            sensitive: false,
+            value_type: None,
+            path: path.map(str::to_string),
+        }
+    }
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn test_build_report() {
+        let prefixes = strings(&["/shared/", "/studio-dev/"]);
+        let entries = vec![
+            entry("DB_HOST", None, None),
+            entry("DB_URL", None, Some("/infra/db/url")),
+            entry("API_KEY", None, None),
+            entry("SENTRY_DSN", Some(false), None),
+            entry("SITE_URL", None, Some("site-url")),
+        ];
+        let listed = strings(&["/shared/DB_HOST", "/studio-dev/OLD_FLAG", "/studio-dev/site-url", "/shared/API_KEY"]);
+        let mut existing: HashSet<String> = listed.iter().cloned().collect();
+        existing.insert("/infra/db/url".to_string());
+
+        let report = CheckReport::build(&entries, &prefixes, &PathMapping::default(), &existing, &listed, &[], true);
+
+        assert_eq!(
+            report.missing,
+            vec![MissingKey {
+                key: "SENTRY_DSN".to_string(),
+                paths: strings(&["/shared/SENTRY_DSN", "/studio-dev/SENTRY_DSN"]),
+                optional: true,
+            }]
+        );
+        assert_eq!(report.orphans, strings(&["/studio-dev/OLD_FLAG"]));
+        assert_eq!(report.missing_required(), 0);
+        assert_eq!(
+            report.render(),
+            "missing (optional): SENTRY_DSN (looked up /shared/SENTRY_DSN, /studio-dev/SENTRY_DSN)\n\
+             orphan: /studio-dev/OLD_FLAG\n"
+        );
+    }
+
+    #[test]
+    fn test_missing_required() {
+        let prefixes = strings(&["/app/"]);
+        let entries = vec![entry("DB_HOST", None, None), entry("DB_PORT", Some(true), None)];
+
+        let report = CheckReport::build(&entries, &prefixes, &PathMapping::default(), &HashSet::new(), &[], &[], true);
+
+        assert_eq!(report.missing_required(), 2);
+        assert!(report.orphans.is_empty());
+    }
+
+    #[test]
+    fn test_required_like_a_normal_run() {
+        let prefixes = strings(&["/app/"]);
+        let mut debug = entry("DEBUG", None, None);
+        debug.commented = true;
+        let entries = vec![entry("DB_HOST", None, None), entry("DB_PORT", Some(true), None), debug];
+
+        let report = CheckReport::build(&entries, &prefixes, &PathMapping::default(), &HashSet::new(), &[], &[], false);
+
+        assert_eq!(report.missing.len(), 3);
+        assert_eq!(report.missing_required(), 1);
+        assert!(report.render().contains("missing (optional): DEBUG"));
+    }
+
+    #[test]
+    fn test_ignored_keys() {
+        let prefixes = strings(&["/app/"]);
+        let entries = vec![entry("DB_HOST", None, None), entry("LOCAL_DEBUG", Some(true), None)];
+        let listed = strings(&["/app/DB_HOST"]);
+        let existing: HashSet<String> = listed.iter().cloned().collect();
+
+        let ignore_keys = strings(&["LOCAL_DEBUG", "DB_HOST"]);
+
+        let report = CheckReport::build(&entries, &prefixes, &PathMapping::default(), &existing, &listed, &ignore_keys, true);
+
+        assert_eq!(report, CheckReport::default());
+    }
+
+    #[test]
+    fn test_is_listed() {
+        let prefixes = strings(&["/app/", "/shared"]);
+
+        assert!(is_listed("/app/DB_HOST", &prefixes));
+        assert!(is_listed("/shared/DB_HOST", &prefixes));
+        assert!(!is_listed("/app/db/url", &prefixes));
+        assert!(!is_listed("/infra/DB_URL", &prefixes));
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::secret_masker::MaskingRules;
+
+/// Config file read when `--config` isn't given, if it exists.
+pub const DEFAULT_CONFIG: &str = "psenv.toml";
+
+/// Settings from a TOML config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub masking: MaskingRules,
+}
+
+impl Config {
+    /// Loads `path`, or [`DEFAULT_CONFIG`] when there's no path and it exists.
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None if Path::new(DEFAULT_CONFIG).exists() => DEFAULT_CONFIG,
+            None => return Ok(Config::default()),
+        };
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file: {}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("psenv.toml");
+
+        fs::write(&path, "[masking]\npatterns = [\"^STRIPE_\"]\nallow = [\"GIT_SHA\"]\n").unwrap();
+        let config = Config::load(path.to_str()).unwrap();
+        assert_eq!(config.masking.patterns, vec!["^STRIPE_"]);
+        assert_eq!(config.masking.allow, vec!["GIT_SHA"]);
+        assert!(config.masking.deny.is_empty());
+
+        fs::write(&path, "[masking]\nalow = [\"GIT_SHA\"]\n").unwrap();
+        assert!(Config::load(path.to_str()).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::Result;
+use std::io::{self, BufRead, Write};
+
+use crate::secret_masker::SecretMasker;
+
+/// How to settle a key whose local value differs from the fetched one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    KeepLocal,
+    TakeRemote,
+    Edit(String),
+    /// Keep the local value for this and every remaining conflict.
+    SkipAll,
+}
+
+/// Decides conflicts for the interactive strategy.
+pub trait ConflictResolver {
+    fn resolve(&mut self, key: &str, local: &str, remote: &str) -> Result<Resolution>;
+}
+
+/// Asks on a terminal, showing masked previews of both values.
+pub struct PromptResolver<R, W> {
+    input: R,
+    output: W,
+    masker: SecretMasker,
+    show_secrets: bool,
+}
+
+impl PromptResolver<io::StdinLock<'static>, io::Stderr> {
+    /// Reads answers from stdin and prompts on stderr, leaving stdout alone.
+    pub fn stdio(masker: SecretMasker, show_secrets: bool) -> Self {
+        Self::new(io::stdin().lock(), io::stderr(), masker, show_secrets)
+    }
+}
+
+impl<R: BufRead, W: Write> PromptResolver<R, W> {
+    pub fn new(input: R, output: W, masker: SecretMasker, show_secrets: bool) -> Self {
+        PromptResolver {
+            input,
+            output,
+            masker,
+            show_secrets,
+        }
+    }
+
+    fn preview(&self, key: &str, value: &str) -> String {
+        if self.show_secrets || !self.masker.is_sensitive(key, value) {
+            value.to_string()
+        } else {
+            self.masker.mask(key, value)
+        }
+    }
+
+    /// Returns `None` at end of input.
+    fn read_line(&mut self) -> Result<Option<String>> {
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    }
+}
+
+impl<R: BufRead, W: Write> ConflictResolver for PromptResolver<R, W> {
+    fn resolve(&mut self, key: &str, local: &str, remote: &str) -> Result<Resolution> {
+        writeln!(self.output, "{} differs from Parameter Store", key)?;
+        writeln!(self.output, "  local:  {}", self.preview(key, local))?;
+        writeln!(self.output, "  remote: {}", self.preview(key, remote))?;
+
+        loop {
+            write!(self.output, "[k]eep local, take [r]emote, [e]dit, [s]kip all? [k] ")?;
+            self.output.flush()?;
+
+            let Some(answer) = self.read_line()? else {
+                return Ok(Resolution::SkipAll);
+            };
+
+            match answer.trim().to_lowercase().as_str() {
+                "" | "k" | "keep" => return Ok(Resolution::KeepLocal),
+                "r" | "remote" => return Ok(Resolution::TakeRemote),
+                "s" | "skip" => return Ok(Resolution::SkipAll),
+                "e" | "edit" => {
+                    write!(self.output, "New value for {}: ", key)?;
+                    self.output.flush()?;
+                    return Ok(match self.read_line()? {
+                        Some(value) => Resolution::Edit(value),
+                        None => Resolution::SkipAll,
+                    });
+                }
+                _ => writeln!(self.output, "Please answer k, r, e or s")?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(answers: &str, key: &str) -> (Resolution, String) {
+        let mut output = Vec::new();
+        let resolution = PromptResolver::new(answers.as_bytes(), &mut output, SecretMasker::new(), false)
+            .resolve(key, "local-value", "remote")
+            .unwrap();
+
+        (resolution, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_prompt_masks_sensitive_values() {
+        let (resolution, output) = prompt("r\n", "API_TOKEN");
+
+        assert_eq!(resolution, Resolution::TakeRemote);
+        assert!(output.contains("  local:  ****** (11 chars, hidden)\n"));
+        assert!(output.contains("  remote: ****** (6 chars, hidden)\n"));
+        assert!(!output.contains("local-value"));
+
+        let (_, output) = prompt("\n", "DATABASE_HOST");
+        assert!(output.contains("  local:  local-value\n"));
+    }
+
+    #[test]
+    fn test_prompt_answers() {
+        assert_eq!(prompt("\n", "DEBUG").0, Resolution::KeepLocal);
+        assert_eq!(prompt("S\n", "DEBUG").0, Resolution::SkipAll);
+        assert_eq!(prompt("e\n value with spaces \n", "DEBUG").0, Resolution::Edit(" value with spaces ".to_string()));
+        assert_eq!(prompt("", "DEBUG").0, Resolution::SkipAll);
+
+        let (resolution, output) = prompt("x\nk\n", "DEBUG");
+        assert_eq!(resolution, Resolution::KeepLocal);
+        assert!(output.contains("Please answer k, r, e or s"));
+    }
+}
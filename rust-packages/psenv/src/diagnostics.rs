@@ -0,0 +1,150 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// The kinds of problems found while parsing templates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticCode {
+    InvalidAnnotation,
+    UnknownAnnotation,
+    DanglingAnnotation,
+    MalformedLine,
+    SkippedKey,
+    DuplicateKey,
+    ConflictingAnnotation,
+    CommentedAndUncommented,
+}
+
+impl DiagnosticCode {
+    pub fn severity(&self) -> Severity {
+        match self {
+            DiagnosticCode::InvalidAnnotation
+            | DiagnosticCode::MalformedLine
+            | DiagnosticCode::DuplicateKey
+            | DiagnosticCode::ConflictingAnnotation => Severity::Error,
+            DiagnosticCode::UnknownAnnotation
+            | DiagnosticCode::DanglingAnnotation
+            | DiagnosticCode::SkippedKey
+            | DiagnosticCode::CommentedAndUncommented => Severity::Warning,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiagnosticCode::InvalidAnnotation => "invalid-annotation",
+            DiagnosticCode::UnknownAnnotation => "unknown-annotation",
+            DiagnosticCode::DanglingAnnotation => "dangling-annotation",
+            DiagnosticCode::MalformedLine => "malformed-line",
+            DiagnosticCode::SkippedKey => "skipped-key",
+            DiagnosticCode::DuplicateKey => "duplicate-key",
+            DiagnosticCode::ConflictingAnnotation => "conflicting-annotation",
+            DiagnosticCode::CommentedAndUncommented => "commented-and-uncommented",
+        }
+    }
+}
+
+/// A problem at a position in a template file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub code: DiagnosticCode,
+    pub message: String,
+    pub source: String,
+    pub line: usize,
+    /// 1-based column, in characters.
+    pub column: usize,
+    /// Number of characters to underline.
+    pub width: usize,
+    pub snippet: String,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.code.severity()
+    }
+
+    pub fn location(&self) -> String {
+        format!("{}:{}:{}", self.source, self.line, self.column)
+    }
+
+    /// Renders the diagnostic with its source line, in the style of rustc.
+    pub fn render(&self) -> String {
+        let severity = match self.severity() {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let gutter = " ".repeat(self.line.to_string().len());
+        let indent = " ".repeat(self.column.saturating_sub(1));
+        let underline = "^".repeat(self.width.max(1));
+
+        let mut output = format!(
+            "{}[{}]: {}\n{}--> {}\n{} |\n{} | {}\n{} | {}{}\n",
+            severity,
+            self.code.name(),
+            self.message,
+            gutter,
+            self.location(),
+            gutter,
+            self.line,
+            self.snippet,
+            gutter,
+            indent,
+            underline,
+        );
+
+        for note in &self.notes {
+            output.push_str(&format!("{} = note: {}\n", gutter, note));
+        }
+
+        output
+    }
+}
+
+/// One-line form, used when diagnostics are logged during a normal run.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location(), self.message)?;
+        for note in &self.notes {
+            write!(f, " ({})", note)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let diagnostic = Diagnostic {
+            code: DiagnosticCode::DuplicateKey,
+            message: "`DB_HOST` is defined again with a different default".to_string(),
+            source: ".env.example".to_string(),
+            line: 12,
+            column: 3,
+            width: 7,
+            snippet: "# DB_HOST=other".to_string(),
+            notes: vec!["first defined at .env.example:2:1".to_string()],
+        };
+
+        assert_eq!(
+            diagnostic.render(),
+            "error[duplicate-key]: `DB_HOST` is defined again with a different default\n\
+             \x20 --> .env.example:12:3\n\
+             \x20  |\n\
+             12 | # DB_HOST=other\n\
+             \x20  |   ^^^^^^^\n\
+             \x20  = note: first defined at .env.example:2:1\n"
+        );
+
+        assert_eq!(
+            diagnostic.to_string(),
+            ".env.example:12:3: `DB_HOST` is defined again with a different default (first defined at .env.example:2:1)"
+        );
+    }
+}
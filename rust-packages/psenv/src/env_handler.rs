@@ -1,10 +1,17 @@
 use anyhow::{Context, Result};
 use clap::ValueEnum;
 use log::{debug, info};
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
+use std::time::Duration;
 
+use crate::backup::create_backup;
+use crate::conflict::{ConflictResolver, Resolution};
+use crate::key_grammar::KeyGrammar;
+use crate::lock::OutputLock;
+use crate::output_file::{write_atomic, FileOptions};
 use crate::PsenvError;
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -15,13 +22,84 @@ pub enum Strategy {
     Overwrite,
     #[value(name = "error")]
     Error,
+    /// Like update, but also prunes keys that are no longer in the template
+    #[value(name = "sync")]
+    Sync,
+    /// Only add missing keys, never replace values already in the file
+    #[value(name = "keep-local")]
+    KeepLocal,
+    /// Ask how to settle each key whose local value differs from the fetched one
+    #[value(name = "interactive")]
+    Interactive,
 }
 
-pub struct EnvHandler;
+/// What the sync strategy does with keys that are no longer in the template.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PruneMode {
+    /// Comment the line out, keeping the value for reference
+    #[default]
+    #[value(name = "comment")]
+    Comment,
+    /// Delete the line
+    #[value(name = "remove")]
+    Remove,
+}
+
+/// Settings that control how output files are read and written.
+#[derive(Debug, Clone, Default)]
+pub struct EnvOptions {
+    pub key_grammar: KeyGrammar,
+    /// Every key the templates declare, fetched or not. Used by the sync strategy.
+    pub template_keys: HashSet<String>,
+    /// Local-only keys the sync strategy never prunes.
+    pub keep_keys: HashSet<String>,
+    pub prune_mode: PruneMode,
+    pub file: FileOptions,
+    /// How many `.bak.<timestamp>` copies of a modified file to keep; 0 disables backups.
+    pub backup_retention: usize,
+    /// How long to wait for another run to release the output file.
+    pub lock_timeout: Duration,
+}
+
+/// The content an output file would get, and how it differs from before.
+#[derive(Debug)]
+pub struct Plan {
+    /// The current file, if there is one.
+    pub existing: Option<String>,
+    pub content: String,
+    pub summary: MergeSummary,
+    /// Whether the content was merged into the existing file rather than written from scratch.
+    pub merged: bool,
+}
+
+/// What a merge into an existing file changed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MergeSummary {
+    pub updated: Vec<String>,
+    pub added: Vec<String>,
+    pub pruned: Vec<String>,
+    /// Keys whose local value was kept although it differs from the fetched one.
+    pub conflicts: Vec<String>,
+}
+
+pub struct EnvHandler {
+    options: EnvOptions,
+    resolver: Option<RefCell<Box<dyn ConflictResolver>>>,
+}
 
 impl EnvHandler {
     pub fn new() -> Self {
-        EnvHandler
+        Self::with_options(EnvOptions::default())
+    }
+
+    pub fn with_options(options: EnvOptions) -> Self {
+        EnvHandler { options, resolver: None }
+    }
+
+    /// Settles conflicts for the interactive strategy. Without one, local values are kept.
+    pub fn with_resolver(mut self, resolver: Box<dyn ConflictResolver>) -> Self {
+        self.resolver = Some(RefCell::new(resolver));
+        self
     }
 
     pub fn handle_env_file(
@@ -29,91 +107,184 @@ impl EnvHandler {
         output_path: &str,
         values: &HashMap<String, String>,
         strategy: Strategy,
-    ) -> Result<()> {
+    ) -> Result<MergeSummary> {
         debug!("Handling .env file: {} with strategy: {:?}", output_path, strategy);
 
-        let output_exists = Path::new(output_path).exists();
+        // Held for the whole read-merge-write cycle
+        let _lock = OutputLock::acquire(Path::new(output_path), self.options.lock_timeout, &self.options.file)?;
 
-        match strategy {
-            Strategy::Error if output_exists => {
-                return Err(PsenvError::FileExists(
-                    format!("Output file already exists: {}", output_path)
-                ).into());
-            }
-            Strategy::Overwrite => {
-                self.write_env_file(output_path, values)?;
-            }
-            Strategy::Update if output_exists => {
-                self.update_env_file(output_path, values)?;
-            }
-            _ => {
-                // For update when file doesn't exist, just create it
-                self.write_env_file(output_path, values)?;
-            }
+        let plan = self.plan(output_path, values, &strategy)?;
+
+        if let Some(existing_content) = &plan.existing {
+            self.backup(output_path, existing_content, &plan.content)?;
         }
 
+        write_atomic(Path::new(output_path), &plan.content, &self.options.file)
+            .with_context(|| format!("Failed to write .env file: {}", output_path))?;
+
+        self.log_summary(&plan);
         info!("Successfully processed .env file: {}", output_path);
-        Ok(())
+        Ok(plan.summary)
     }
 
-    fn write_env_file(&self, path: &str, values: &HashMap<String, String>) -> Result<()> {
-        debug!("Writing new .env file: {}", path);
+    /// Works out what `handle_env_file` would write, without touching the file.
+    pub fn plan(&self, output_path: &str, values: &HashMap<String, String>, strategy: &Strategy) -> Result<Plan> {
+        let existing = match fs::read_to_string(output_path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).with_context(|| format!("Failed to read existing .env file: {}", output_path)),
+        };
+
+        match (strategy, existing) {
+            (Strategy::Error, Some(_)) => Err(PsenvError::FileExists(
+                format!("Output file already exists: {}", output_path)
+            ).into()),
+            (Strategy::Overwrite, existing) => Ok(self.plan_new_file(values, existing)),
+            (_, Some(existing_content)) => {
+                debug!("Updating existing .env file: {}", output_path);
+                let (content, summary) = self.update_preserve_format(&existing_content, values, strategy)?;
+                Ok(Plan { existing: Some(existing_content), content, summary, merged: true })
+            }
+            // For update when file doesn't exist, just create it
+            (_, None) => Ok(self.plan_new_file(values, None)),
+        }
+    }
 
+    fn plan_new_file(&self, values: &HashMap<String, String>, existing: Option<String>) -> Plan {
         let mut content = String::new();
         let mut sorted_keys: Vec<&String> = values.keys().collect();
         sorted_keys.sort();
 
-        for key in sorted_keys {
-            if let Some(value) = values.get(key) {
+        for key in &sorted_keys {
+            if let Some(value) = values.get(*key) {
                 content.push_str(&format!("{}={}\n", key, value));
             }
         }
 
-        fs::write(path, content)
-            .with_context(|| format!("Failed to write .env file: {}", path))?;
-
-        info!("Created new .env file with {} variables", values.len());
-        Ok(())
+        Plan {
+            existing,
+            content,
+            summary: MergeSummary {
+                added: sorted_keys.into_iter().cloned().collect(),
+                ..MergeSummary::default()
+            },
+            merged: false,
+        }
     }
 
+    fn log_summary(&self, plan: &Plan) {
+        let summary = &plan.summary;
 
-    fn update_env_file(&self, path: &str, new_values: &HashMap<String, String>) -> Result<()> {
-        debug!("Updating existing .env file: {}", path);
-
-        let existing_content = fs::read_to_string(path)
-            .with_context(|| format!("Failed to read existing .env file: {}", path))?;
+        if !plan.merged {
+            info!("Created new .env file with {} variables", summary.added.len());
+            return;
+        }
 
-        let (updated_content, updated_count, added_count) = self.update_preserve_format(&existing_content, new_values)?;
+        info!("Updated .env file: updated {} variables, added {} variables", summary.updated.len(), summary.added.len());
+        if !summary.pruned.is_empty() {
+            let action = match self.options.prune_mode {
+                PruneMode::Comment => "commented out",
+                PruneMode::Remove => "removed",
+            };
+            info!("Pruned {} keys no longer in the template ({}): {}", summary.pruned.len(), action, summary.pruned.join(", "));
+        }
+        if !summary.conflicts.is_empty() {
+            info!("Kept {} local values that differ from Parameter Store: {}", summary.conflicts.len(), summary.conflicts.join(", "));
+        }
+    }
 
-        fs::write(path, updated_content)
-            .with_context(|| format!("Failed to write .env file: {}", path))?;
+    /// Saves the current file before it is replaced with different content.
+    fn backup(&self, path: &str, existing_content: &str, new_content: &str) -> Result<()> {
+        if self.options.backup_retention == 0 || existing_content == new_content {
+            return Ok(());
+        }
 
-        info!("Updated .env file: updated {} variables, added {} variables", updated_count, added_count);
+        let backup = create_backup(Path::new(path), existing_content, self.options.backup_retention, &self.options.file)?;
+        info!("Backed up previous {} to {}", path, backup.display());
         Ok(())
     }
 
-
-
-    fn update_preserve_format(&self, content: &str, new_values: &HashMap<String, String>) -> Result<(String, usize, usize)> {
-        use regex::Regex;
-        let env_key_regex = Regex::new(r"^#?\s*([A-Z_][A-Z0-9_]*)\s*=.*$").unwrap();
+    fn update_preserve_format(
+        &self,
+        content: &str,
+        new_values: &HashMap<String, String>,
+        strategy: &Strategy,
+    ) -> Result<(String, MergeSummary)> {
+        let key_grammar = &self.options.key_grammar;
+        let prune = matches!(strategy, Strategy::Sync);
+        let keep_local = matches!(strategy, Strategy::KeepLocal);
+        let interactive = matches!(strategy, Strategy::Interactive);
+        let mut resolver = self.resolver.as_ref().filter(|_| interactive).map(RefCell::borrow_mut);
+        let mut skip_all = false;
 
         let mut existing_keys = HashSet::new();
         let mut result = String::new();
-        let mut updated_count = 0;
+        let mut summary = MergeSummary::default();
+
+        // A commented-out copy of a key the file sets must not take over from the local value
+        let active_keys: HashSet<&str> = content.lines()
+            .filter_map(|line| key_grammar.parse_assignment(line))
+            .filter(|assignment| !assignment.commented)
+            .map(|assignment| assignment.name)
+            .collect();
 
         // Process existing content line by line, replacing values where needed
         for line in content.lines() {
-            if let Some(captures) = env_key_regex.captures(line) {
-                if let Some(key_match) = captures.get(1) {
-                    let key = key_match.as_str();
+            if let Some(assignment) = key_grammar.parse_assignment(line) {
+                let key = assignment.name;
+                if key_grammar.is_key(key) {
                     existing_keys.insert(key.to_string());
 
-                    if let Some(new_value) = new_values.get(key) {
-                        // Replace with new value
-                        result.push_str(&format!("{}={}\n", key, new_value));
-                        updated_count += 1;
-                        debug!("Updated existing variable: {}", key);
+                    match new_values.get(key) {
+                        // Left alone, the active line below or above decides
+                        Some(_) if (keep_local || interactive) && assignment.commented && active_keys.contains(key) => {}
+                        // Active local values are only replaced when the resolver says so
+                        Some(new_value) if (keep_local || interactive) && !assignment.commented => {
+                            let local_value = unquote(assignment.value);
+                            if local_value != new_value {
+                                let resolution = match resolver.as_mut() {
+                                    Some(resolver) if !skip_all => resolver.resolve(key, local_value, new_value)?,
+                                    _ => Resolution::KeepLocal,
+                                };
+
+                                let replacement = match resolution {
+                                    Resolution::TakeRemote => Some(new_value.clone()),
+                                    Resolution::Edit(value) => Some(value),
+                                    Resolution::KeepLocal => None,
+                                    Resolution::SkipAll => {
+                                        skip_all = true;
+                                        None
+                                    }
+                                };
+
+                                if let Some(value) = replacement {
+                                    result.push_str(&assignment_line(assignment.exported, key, &value));
+                                    summary.updated.push(key.to_string());
+                                    debug!("Replaced local value: {}", key);
+                                    continue;
+                                }
+
+                                debug!("Keeping local value that differs from Parameter Store: {}", key);
+                                if !summary.conflicts.iter().any(|k| k == key) {
+                                    summary.conflicts.push(key.to_string());
+                                }
+                            }
+                        }
+                        Some(new_value) => {
+                            result.push_str(&assignment_line(assignment.exported, key, new_value));
+                            summary.updated.push(key.to_string());
+                            debug!("Updated existing variable: {}", key);
+                            continue;
+                        }
+                        None => {}
+                    }
+
+                    if prune && !assignment.commented && self.is_stale(key) {
+                        if self.options.prune_mode == PruneMode::Comment {
+                            result.push_str(&format!("# {}\n", line));
+                        }
+                        summary.pruned.push(key.to_string());
+                        debug!("Pruned variable no longer in the template: {}", key);
                         continue;
                     }
                 }
@@ -124,7 +295,6 @@ impl EnvHandler {
         }
 
         // Add new keys that don't exist
-        let mut added_count = 0;
         let mut new_keys: Vec<&String> = new_values.keys().collect();
         new_keys.sort();
 
@@ -132,14 +302,33 @@ impl EnvHandler {
             if !existing_keys.contains(key) {
                 if let Some(value) = new_values.get(key) {
                     result.push_str(&format!("{}={}\n", key, value));
-                    added_count += 1;
+                    summary.added.push(key.clone());
                     debug!("Added new variable: {}", key);
                 }
             }
         }
 
-        Ok((result, updated_count, added_count))
+        Ok((result, summary))
     }
+
+    fn is_stale(&self, key: &str) -> bool {
+        !self.options.template_keys.contains(key) && !self.options.keep_keys.contains(key)
+    }
+}
+
+/// Formats a replaced line, keeping a shell `export` prefix.
+fn assignment_line(exported: bool, key: &str, value: &str) -> String {
+    let export = if exported { "export " } else { "" };
+    format!("{}{}={}\n", export, key, value)
+}
+
+/// The value of an assignment as written, without surrounding whitespace or quotes.
+fn unquote(raw: &str) -> &str {
+    let value = raw.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+        .unwrap_or(value)
 }
 
 impl Default for EnvHandler {
@@ -151,19 +340,22 @@ impl Default for EnvHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::key_grammar::KeyGrammarKind;
     use std::fs;
     use tempfile::NamedTempFile;
 
     #[test]
     fn test_write_env_file() {
         let handler = EnvHandler::new();
-        let temp_file = NamedTempFile::new().unwrap();
+        // The lock file is left next to the output, so keep it out of /tmp
+        let dir = tempfile::tempdir().unwrap();
+        let temp_file = NamedTempFile::new_in(dir.path()).unwrap();
 
         let mut values = HashMap::new();
         values.insert("KEY1".to_string(), "value1".to_string());
         values.insert("KEY2".to_string(), "value2".to_string());
 
-        handler.write_env_file(temp_file.path().to_str().unwrap(), &values).unwrap();
+        handler.handle_env_file(temp_file.path().to_str().unwrap(), &values, Strategy::Overwrite).unwrap();
 
         let content = fs::read_to_string(temp_file.path()).unwrap();
         assert!(content.contains("KEY1=value1"));
@@ -174,7 +366,8 @@ mod tests {
     #[test]
     fn test_update_env_file() {
         let handler = EnvHandler::new();
-        let temp_file = NamedTempFile::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let temp_file = NamedTempFile::new_in(dir.path()).unwrap();
 
         // Create initial .env file with comments
         let initial_content = "# Configuration\nEXISTING_KEY=existing_value\nANOTHER_KEY=another_value\n# End\n";
@@ -184,7 +377,7 @@ mod tests {
         new_values.insert("NEW_KEY".to_string(), "new_value".to_string());
         new_values.insert("EXISTING_KEY".to_string(), "updated_value".to_string());
 
-        handler.update_env_file(temp_file.path().to_str().unwrap(), &new_values).unwrap();
+        handler.handle_env_file(temp_file.path().to_str().unwrap(), &new_values, Strategy::Update).unwrap();
 
         let content = fs::read_to_string(temp_file.path()).unwrap();
 
@@ -199,4 +392,181 @@ mod tests {
         // Should add new key at the end
         assert!(content.contains("NEW_KEY=new_value"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_backup_before_modifying() {
+        let handler = EnvHandler::with_options(EnvOptions {
+            backup_retention: 2,
+            ..EnvOptions::default()
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        let output = path.to_str().unwrap();
+        fs::write(&path, "A=local\n").unwrap();
+
+        let mut values = HashMap::new();
+        values.insert("A".to_string(), "remote".to_string());
+
+        handler.handle_env_file(output, &values, Strategy::Update).unwrap();
+        // Nothing changes the second time, so there's nothing to back up
+        handler.handle_env_file(output, &values, Strategy::Overwrite).unwrap();
+
+        let backups = crate::backup::list_backups(&path).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), "A=local\n");
+    }
+
+    #[test]
+    fn test_update_env_file_with_key_grammar() {
+        let handler = EnvHandler::with_options(EnvOptions {
+            key_grammar: KeyGrammar::new(KeyGrammarKind::Identifier),
+            ..EnvOptions::default()
+        });
+
+        let content = "nextAuthUrl=http://old\nvite_public_x=1\n";
+
+        let mut new_values = HashMap::new();
+        new_values.insert("nextAuthUrl".to_string(), "http://new".to_string());
+
+        let (updated, summary) = handler.update_preserve_format(content, &new_values, &Strategy::Update).unwrap();
+
+        assert_eq!(updated, "nextAuthUrl=http://new\nvite_public_x=1\n");
+        assert_eq!((summary.updated.len(), summary.added.len()), (1, 0));
+    }
+
+    #[test]
+    fn test_update_keeps_export_prefix() {
+        let handler = EnvHandler::new();
+
+        let content = "export DB_HOST=old\nexport  DB_PORT=5432\nDEBUG=true\n";
+
+        let mut new_values = HashMap::new();
+        new_values.insert("DB_HOST".to_string(), "db.internal".to_string());
+        new_values.insert("DEBUG".to_string(), "false".to_string());
+        new_values.insert("NEW_KEY".to_string(), "value".to_string());
+
+        let (updated, summary) = handler.update_preserve_format(content, &new_values, &Strategy::Update).unwrap();
+
+        assert_eq!(updated, "export DB_HOST=db.internal\nexport  DB_PORT=5432\nDEBUG=false\nNEW_KEY=value\n");
+        assert_eq!((summary.updated.len(), summary.added.len()), (2, 1));
+    }
+
+    fn sync_handler(prune_mode: PruneMode) -> EnvHandler {
+        EnvHandler::with_options(EnvOptions {
+            template_keys: ["DB_HOST", "API_KEY"].iter().map(|key| key.to_string()).collect(),
+            keep_keys: ["LOCAL_ONLY"].iter().map(|key| key.to_string()).collect(),
+            prune_mode,
+            ..EnvOptions::default()
+        })
+    }
+
+    #[test]
+    fn test_sync_prunes_stale_keys() {
+        let content = "# Config\nDB_HOST=old\nOLD_FLAG=1\nexport REMOVED_URL=http://x\n# DISABLED=1\nLOCAL_ONLY=me\nAPI_KEY=local\n";
+
+        let mut new_values = HashMap::new();
+        new_values.insert("DB_HOST".to_string(), "db.internal".to_string());
+
+        let (updated, summary) = sync_handler(PruneMode::Comment)
+            .update_preserve_format(content, &new_values, &Strategy::Sync)
+            .unwrap();
+
+        // API_KEY is in the template but wasn't fetched, so it stays
+        assert_eq!(
+            updated,
+            "# Config\nDB_HOST=db.internal\n# OLD_FLAG=1\n# export REMOVED_URL=http://x\n# DISABLED=1\nLOCAL_ONLY=me\nAPI_KEY=local\n"
+        );
+        assert_eq!(summary.pruned, vec!["OLD_FLAG".to_string(), "REMOVED_URL".to_string()]);
+
+        let (updated, _) = sync_handler(PruneMode::Remove)
+            .update_preserve_format(content, &new_values, &Strategy::Sync)
+            .unwrap();
+        assert_eq!(updated, "# Config\nDB_HOST=db.internal\n# DISABLED=1\nLOCAL_ONLY=me\nAPI_KEY=local\n");
+    }
+
+    #[test]
+    fn test_keep_local_only_adds_missing_keys() {
+        let handler = EnvHandler::new();
+        let content = "# DATABASE_URL=postgres://rds/app\nDATABASE_URL=postgres://localhost:5432/app\nDB_PORT=\"5432\"\n# API_URL=http://old\n";
+
+        let mut new_values = HashMap::new();
+        new_values.insert("DATABASE_URL".to_string(), "postgres://rds/app".to_string());
+        new_values.insert("DB_PORT".to_string(), "5432".to_string());
+        new_values.insert("API_URL".to_string(), "https://api".to_string());
+        new_values.insert("NEW_KEY".to_string(), "value".to_string());
+
+        let (updated, summary) = handler.update_preserve_format(content, &new_values, &Strategy::KeepLocal).unwrap();
+
+        assert_eq!(
+            updated,
+            "# DATABASE_URL=postgres://rds/app\nDATABASE_URL=postgres://localhost:5432/app\nDB_PORT=\"5432\"\nAPI_URL=https://api\nNEW_KEY=value\n"
+        );
+        assert_eq!(summary.conflicts, vec!["DATABASE_URL".to_string()]);
+        assert_eq!(summary.updated, vec!["API_URL".to_string()]);
+        assert_eq!(summary.added, vec!["NEW_KEY".to_string()]);
+    }
+
+    /// Answers conflicts from a fixed list.
+    struct ScriptedResolver {
+        answers: Vec<Resolution>,
+        asked: std::rc::Rc<RefCell<Vec<String>>>,
+    }
+
+    impl ConflictResolver for ScriptedResolver {
+        fn resolve(&mut self, key: &str, _local: &str, _remote: &str) -> Result<Resolution> {
+            self.asked.borrow_mut().push(key.to_string());
+            Ok(self.answers.remove(0))
+        }
+    }
+
+    #[test]
+    fn test_interactive_resolves_conflicts() {
+        let asked = std::rc::Rc::new(RefCell::new(Vec::new()));
+        let handler = EnvHandler::new().with_resolver(Box::new(ScriptedResolver {
+            answers: vec![
+                Resolution::TakeRemote,
+                Resolution::Edit("edited".to_string()),
+                Resolution::KeepLocal,
+                Resolution::SkipAll,
+            ],
+            asked: asked.clone(),
+        }));
+        let content = "A=1\nB=1\nSAME=x\nC=1\nexport D=1\nE=1\n";
+
+        let new_values: HashMap<String, String> = ["A", "B", "C", "D", "E"].iter()
+            .map(|key| (key.to_string(), "2".to_string()))
+            .chain([("SAME".to_string(), "x".to_string())])
+            .collect();
+
+        let (updated, summary) = handler.update_preserve_format(content, &new_values, &Strategy::Interactive).unwrap();
+
+        // E is never asked about after skip all
+        assert_eq!(*asked.borrow(), vec!["A", "B", "C", "D"]);
+        assert_eq!(updated, "A=2\nB=edited\nSAME=x\nC=1\nexport D=1\nE=1\n");
+        assert_eq!(summary.conflicts, vec!["C".to_string(), "D".to_string(), "E".to_string()]);
+        assert_eq!(summary.updated, vec!["A".to_string(), "B".to_string()]);
+    }
+
+    #[test]
+    fn test_interactive_without_resolver_keeps_local() {
+        let mut new_values = HashMap::new();
+        new_values.insert("A".to_string(), "2".to_string());
+
+        let (updated, summary) = EnvHandler::new()
+            .update_preserve_format("A=1\n", &new_values, &Strategy::Interactive)
+            .unwrap();
+
+        assert_eq!(updated, "A=1\n");
+        assert_eq!(summary.conflicts, vec!["A".to_string()]);
+    }
+
+    #[test]
+    fn test_update_never_prunes() {
+        let (updated, summary) = sync_handler(PruneMode::Remove)
+            .update_preserve_format("OLD_FLAG=1\n", &HashMap::new(), &Strategy::Update)
+            .unwrap();
+
+        assert_eq!(updated, "OLD_FLAG=1\n");
+        assert!(summary.pruned.is_empty());
+    }
+}
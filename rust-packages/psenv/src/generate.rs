@@ -0,0 +1,229 @@
+use log::warn;
+use std::collections::HashSet;
+
+use crate::aws_client::ListedParameter;
+use crate::key_grammar::KeyGrammar;
+use crate::secret_masker::SecretMasker;
+
+/// Renders a template from the parameters found under a prefix.
+///
+/// Secure strings and keys that look sensitive get a `@sensitive` annotation
+/// and the placeholder instead of their value; everything else keeps its
+/// current value as the default. Parameter descriptions become comments.
+pub struct TemplateGenerator {
+    key_grammar: KeyGrammar,
+    masker: SecretMasker,
+    placeholder: String,
+}
+
+impl TemplateGenerator {
+    pub fn new(key_grammar: KeyGrammar, placeholder: &str) -> Self {
+        TemplateGenerator {
+            key_grammar,
+            masker: SecretMasker::new(),
+            placeholder: placeholder.to_string(),
+        }
+    }
+
+    /// Decides which keys are sensitive with `masker` instead of the default rules.
+    pub fn with_masker(mut self, masker: SecretMasker) -> Self {
+        self.masker = masker;
+        self
+    }
+
+    /// Prefixes description lines the template parser would read as an
+    /// annotation or a commented-out key, so they stay plain comments.
+    fn escape_comment(&self, line: &str) -> String {
+        let comment = format!("# {}", line);
+        if line.starts_with('@') || self.key_grammar.parse_assignment(&comment).is_some() {
+            format!("| {}", line)
+        } else {
+            line.to_string()
+        }
+    }
+
+    pub fn render(&self, prefix: &str, parameters: &[ListedParameter]) -> String {
+        let mut output = format!("# Generated by psenv from {}\n", prefix);
+        let mut seen = HashSet::new();
+
+        for parameter in parameters {
+            let leaf = parameter.name.rsplit('/').next().unwrap_or(&parameter.name);
+
+            let Some(key) = self.key_for(leaf) else {
+                warn!("Skipping {}, whose name cannot be turned into a key", parameter.name);
+                continue;
+            };
+
+            if !seen.insert(key.clone()) {
+                warn!("Skipping {}, which maps to the key {} that is already generated", parameter.name, key);
+                continue;
+            }
+
+            output.push('\n');
+
+            if let Some(description) = &parameter.description {
+                for line in description.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                    output.push_str(&format!("# {}\n", self.escape_comment(line)));
+                }
+            }
+
+            let sensitive = parameter.secure
+                || self.masker.is_sensitive(&key, parameter.value.as_deref().unwrap_or(""));
+            if sensitive {
+                output.push_str("# @sensitive\n");
+            }
+            if key != leaf {
+                output.push_str(&format!("# @path={}\n", leaf));
+            }
+
+            let value = if sensitive {
+                self.placeholder.as_str()
+            } else {
+                match parameter.value.as_deref() {
+                    Some(value) if value.contains('\n') => {
+                        warn!("Leaving {} empty, multi-line values can't be written as a default", key);
+                        ""
+                    }
+                    Some(value) => value,
+                    None => "",
+                }
+            };
+
+            output.push_str(&format!("{}={}\n", key, quote(value)));
+        }
+
+        output
+    }
+
+    /// Uses the parameter name as the key, or its upper-snake form with a `@path` annotation.
+    fn key_for(&self, leaf: &str) -> Option<String> {
+        if self.key_grammar.is_key(leaf) {
+            return Some(leaf.to_string());
+        }
+
+        let mut key: String = leaf
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+            .collect();
+        if key.starts_with(|c: char| c.is_ascii_digit()) {
+            key.insert(0, '_');
+        }
+
+        Some(key).filter(|key| self.key_grammar.is_key(key))
+    }
+}
+
+/// Quotes values the template parser would otherwise trim or mistake for a comment.
+fn quote(value: &str) -> String {
+    let needs_quotes = value != value.trim() || value.contains(['#', '"', '\'']);
+
+    if !needs_quotes {
+        value.to_string()
+    } else if value.contains('"') {
+        format!("'{}'", value)
+    } else {
+        format!("\"{}\"", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template_loader::TemplateReader;
+    use std::fs;
+
+    fn parameter(name: &str, value: Option<&str>, secure: bool, description: Option<&str>) -> ListedParameter {
+        ListedParameter {
+            name: name.to_string(),
+            value: value.map(str::to_string),
+            secure,
+            description: description.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        let generator = TemplateGenerator::new(KeyGrammar::default(), "");
+        let parameters = vec![
+            parameter("/studio-dev/DATABASE_URL", None, true, Some("Primary Postgres connection string")),
+            parameter("/studio-dev/GREETING", Some("hello world # not a comment"), false, None),
+            parameter("/studio-dev/LOG_LEVEL", Some("info"), false, Some("One of\ndebug, info, warn")),
+            parameter("/studio-dev/STRIPE_SECRET", Some("plain-but-secret"), false, None),
+            parameter("/studio-dev/site-url", Some("http://localhost:3000"), false, None),
+        ];
+
+        assert_eq!(
+            generator.render("/studio-dev/", &parameters),
+            "# Generated by psenv from /studio-dev/\n\
+             \n\
+             # Primary Postgres connection string\n\
+             # @sensitive\n\
+             DATABASE_URL=\n\
+             \n\
+             GREETING=\"hello world # not a comment\"\n\
+             \n\
+             # One of\n\
+             # debug, info, warn\n\
+             LOG_LEVEL=info\n\
+             \n\
+             # @sensitive\n\
+             STRIPE_SECRET=\n\
+             \n\
+             # @path=site-url\n\
+             SITE_URL=http://localhost:3000\n"
+        );
+    }
+
+    #[test]
+    fn test_render_skips_unusable_names() {
+        let generator = TemplateGenerator::new(KeyGrammar::default(), "changeme");
+        let parameters = vec![
+            parameter("/app/API_TOKEN", None, true, None),
+            parameter("/app/db-host", Some("localhost"), false, None),
+            parameter("/app/DB_HOST", Some("other"), false, None),
+            parameter("/app/CERT_PEM", Some("-----BEGIN\nabc"), false, None),
+            parameter("/app/BANNER", Some("line one\nline two"), false, None),
+        ];
+
+        let output = generator.render("/app/", &parameters);
+
+        assert!(output.contains("API_TOKEN=changeme\n"));
+        assert!(output.contains("DB_HOST=localhost\n"));
+        assert!(!output.contains("other"));
+        // Sensitive keys get the placeholder, multi-line or not
+        assert!(output.contains("# @sensitive\nCERT_PEM=changeme\n"));
+        assert!(output.contains("BANNER=\n"));
+    }
+
+    #[test]
+    fn test_generated_template_round_trips() {
+        let generator = TemplateGenerator::new(KeyGrammar::default(), "");
+        let parameters = vec![
+            parameter("/app/DATABASE_URL", None, true, Some("Connection string")),
+            parameter("/app/GREETING", Some(" padded 'value' "), false, None),
+            parameter("/app/worker-count", Some("4"), false, None),
+            parameter("/app/WORKER_THREADS", Some("8"), false, Some("@optional in prod\nDEBUG=true enables tracing")),
+        ];
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.example");
+        fs::write(&path, generator.render("/app/", &parameters)).unwrap();
+
+        let parsed = TemplateReader::new().parse(&[path.display().to_string()]).unwrap();
+        assert!(parsed.diagnostics.is_empty());
+
+        let entries = parsed.entries;
+        assert_eq!(entries[0].key, "DATABASE_URL");
+        assert!(entries[0].sensitive);
+        assert_eq!(entries[0].description.as_deref(), Some("Connection string"));
+        assert_eq!(entries[1].default.as_deref(), Some(" padded 'value' "));
+        assert_eq!(entries[2].key, "WORKER_COUNT");
+        assert_eq!(entries[2].path.as_deref(), Some("worker-count"));
+        assert_eq!(entries[2].default.as_deref(), Some("4"));
+        // Descriptions never turn into annotations or keys
+        assert_eq!(entries.len(), 4);
+        let threads = entries.iter().find(|entry| entry.key == "WORKER_THREADS").unwrap();
+        assert_eq!(threads.required, None);
+        assert_eq!(threads.description.as_deref(), Some("| @optional in prod | DEBUG=true enables tracing"));
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use regex::Regex;
+
+/// Built-in rules for which names count as environment variable keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum KeyGrammarKind {
+    /// Upper-case POSIX names: `[A-Z_][A-Z0-9_]*`
+    #[value(name = "posix")]
+    Posix,
+    /// Any identifier, including lower and mixed case: `[A-Za-z_][A-Za-z0-9_]*`
+    #[value(name = "identifier")]
+    Identifier,
+}
+
+/// Decides whether the left-hand side of an assignment is a key.
+#[derive(Debug, Clone)]
+pub struct KeyGrammar {
+    key_regex: Regex,
+    assignment_regex: Regex,
+}
+
+impl KeyGrammar {
+    pub fn new(kind: KeyGrammarKind) -> Self {
+        let pattern = match kind {
+            KeyGrammarKind::Posix => r"[A-Z_][A-Z0-9_]*",
+            KeyGrammarKind::Identifier => r"[A-Za-z_][A-Za-z0-9_]*",
+        };
+
+        Self::build(pattern).unwrap()
+    }
+
+    /// Uses a custom regex; it must match the whole key.
+    pub fn custom(pattern: &str) -> Result<Self> {
+        Self::build(pattern).with_context(|| format!("Invalid key pattern: {}", pattern))
+    }
+
+    fn build(pattern: &str) -> Result<Self> {
+        let key_regex = Regex::new(&format!("^(?:{})$", pattern))?;
+
+        // Anything shaped like `[# ][export ]NAME=value`; NAME is then checked against the grammar
+        let assignment_regex = Regex::new(r"^(#)?\s*(export\s+)?([^\s=#]+)\s*=(.*)$").unwrap();
+
+        Ok(KeyGrammar {
+            key_regex,
+            assignment_regex,
+        })
+    }
+
+    pub fn is_key(&self, name: &str) -> bool {
+        self.key_regex.is_match(name)
+    }
+
+    /// Splits a trimmed line into an assignment, whether or not its name is a valid key.
+    pub fn parse_assignment<'a>(&self, line: &'a str) -> Option<Assignment<'a>> {
+        let captures = self.assignment_regex.captures(line)?;
+
+        Some(Assignment {
+            commented: captures.get(1).is_some(),
+            exported: captures.get(2).is_some(),
+            name: captures.get(3)?.as_str(),
+            value: captures.get(4)?.as_str(),
+        })
+    }
+}
+
+impl Default for KeyGrammar {
+    fn default() -> Self {
+        Self::new(KeyGrammarKind::Posix)
+    }
+}
+
+/// A `NAME=value` line, possibly commented out or prefixed with `export`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Assignment<'a> {
+    pub commented: bool,
+    pub exported: bool,
+    pub name: &'a str,
+    pub value: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_posix_grammar() {
+        let grammar = KeyGrammar::new(KeyGrammarKind::Posix);
+
+        assert!(grammar.is_key("DATABASE_URL"));
+        assert!(grammar.is_key("_PRIVATE"));
+        assert!(!grammar.is_key("nextAuthUrl"));
+        assert!(!grammar.is_key("vite_public_x"));
+        assert!(!grammar.is_key("1KEY"));
+    }
+
+    #[test]
+    fn test_identifier_grammar() {
+        let grammar = KeyGrammar::new(KeyGrammarKind::Identifier);
+
+        assert!(grammar.is_key("DATABASE_URL"));
+        assert!(grammar.is_key("nextAuthUrl"));
+        assert!(grammar.is_key("vite_public_x"));
+        assert!(!grammar.is_key("my-key"));
+    }
+
+    #[test]
+    fn test_custom_grammar() {
+        let grammar = KeyGrammar::custom(r"[A-Za-z_][A-Za-z0-9_.-]*").unwrap();
+
+        assert!(grammar.is_key("my-key"));
+        assert!(grammar.is_key("app.port"));
+        assert!(!grammar.is_key("has space"));
+
+        assert!(KeyGrammar::custom("[unclosed").is_err());
+    }
+
+    #[test]
+    fn test_parse_assignment() {
+        let grammar = KeyGrammar::default();
+
+        assert_eq!(
+            grammar.parse_assignment("# nextAuthUrl = http://localhost"),
+            Some(Assignment {
+                commented: true,
+                exported: false,
+                name: "nextAuthUrl",
+                value: " http://localhost",
+            })
+        );
+        assert_eq!(
+            grammar.parse_assignment("export DB_HOST=localhost"),
+            Some(Assignment {
+                commented: false,
+                exported: true,
+                name: "DB_HOST",
+                value: "localhost",
+            })
+        );
+        assert_eq!(grammar.parse_assignment("export=1").map(|a| a.name), Some("export"));
+        assert_eq!(grammar.parse_assignment("# just a comment"), None);
+    }
+}
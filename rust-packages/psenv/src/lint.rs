@@ -0,0 +1,43 @@
+use anyhow::Result;
+use log::info;
+
+use crate::diagnostics::Severity;
+use crate::template_loader::TemplateReader;
+use crate::PsenvError;
+
+/// Checks templates for problems and prints them as rustc-style diagnostics.
+///
+/// Fails when any error is found, or any warning when `deny_warnings` is set.
+pub fn lint_templates(reader: &TemplateReader, template_paths: &[String], deny_warnings: bool) -> Result<()> {
+    let parsed = reader.parse(template_paths)?;
+
+    let mut errors = 0;
+    let mut warnings = 0;
+
+    for diagnostic in &parsed.diagnostics {
+        eprintln!("{}", diagnostic.render());
+        match diagnostic.severity() {
+            Severity::Error => errors += 1,
+            Severity::Warning => warnings += 1,
+        }
+    }
+
+    if errors > 0 || (deny_warnings && warnings > 0) {
+        return Err(PsenvError::LintFailed(format!(
+            "{} error(s), {} warning(s) in {}",
+            errors,
+            warnings,
+            template_paths.join(", ")
+        ))
+        .into());
+    }
+
+    info!(
+        "Checked {} keys in {}: {} warning(s)",
+        parsed.entries.len(),
+        template_paths.join(", "),
+        warnings
+    );
+
+    Ok(())
+}
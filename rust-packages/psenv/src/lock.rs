@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::output_file::{resolve_target, writable_dir, FileOptions};
+use crate::PsenvError;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An exclusive advisory lock on an output file, held until dropped.
+///
+/// The lock is taken on a `<output>.lock` sidecar rather than the output
+/// itself, because atomic writes replace the output file with a new one.
+/// Like writes, it follows symlinks, so a link and its target share a lock.
+/// Only Unix has advisory locks; elsewhere runs don't wait for each other.
+///
+/// Waiting blocks the thread; async callers should use `block_in_place`.
+pub struct OutputLock {
+    #[cfg(unix)]
+    _lock: nix::fcntl::Flock<File>,
+    #[cfg(not(unix))]
+    _file: File,
+}
+
+enum Attempt {
+    Locked(OutputLock),
+    Busy(File),
+}
+
+impl OutputLock {
+    /// Waits up to `timeout` for other psenv runs to release the output file.
+    ///
+    /// The sidecar is only created in a directory `options` allow writing to.
+    pub fn acquire(path: &Path, timeout: Duration, options: &FileOptions) -> Result<Self> {
+        let target = resolve_target(path)?;
+        writable_dir(path, &target, options)?;
+        let lock_path = lock_path(&target);
+
+        let mut open_options = OpenOptions::new();
+        open_options.read(true).write(true).create(true).truncate(false);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut open_options, crate::output_file::DEFAULT_MODE);
+
+        let mut file = open_options
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open lock file: {}", lock_path.display()))?;
+
+        let start = Instant::now();
+        let mut announced = false;
+
+        loop {
+            file = match try_lock(file).with_context(|| format!("Failed to lock {}", lock_path.display()))? {
+                Attempt::Locked(lock) => {
+                    debug!("Locked {}", lock_path.display());
+                    return Ok(lock);
+                }
+                Attempt::Busy(file) => file,
+            };
+
+            if start.elapsed() >= timeout {
+                return Err(PsenvError::Locked(format!(
+                    "{} is locked by another psenv run (gave up after {}s waiting on {}, see --lock-timeout)",
+                    path.display(),
+                    timeout.as_secs_f32(),
+                    lock_path.display()
+                ))
+                .into());
+            }
+
+            if !announced {
+                info!("Waiting for another psenv run to finish with {}", path.display());
+                announced = true;
+            }
+            thread::sleep(POLL_INTERVAL.min(timeout.saturating_sub(start.elapsed())));
+        }
+    }
+}
+
+#[cfg(unix)]
+fn try_lock(file: File) -> std::io::Result<Attempt> {
+    use nix::errno::Errno;
+    use nix::fcntl::{Flock, FlockArg};
+
+    match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+        Ok(lock) => Ok(Attempt::Locked(OutputLock { _lock: lock })),
+        Err((file, Errno::EWOULDBLOCK)) => Ok(Attempt::Busy(file)),
+        Err((_, errno)) => Err(errno.into()),
+    }
+}
+
+#[cfg(not(unix))]
+fn try_lock(file: File) -> std::io::Result<Attempt> {
+    Ok(Attempt::Locked(OutputLock { _file: file }))
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_times_out_while_held() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+
+        let lock = OutputLock::acquire(&path, Duration::ZERO, &FileOptions::default()).unwrap();
+        assert!(dir.path().join(".env.lock").exists());
+
+        let err = OutputLock::acquire(&path, Duration::from_millis(150), &FileOptions::default()).err().unwrap();
+        assert!(matches!(err.downcast_ref::<PsenvError>(), Some(PsenvError::Locked(_))));
+
+        drop(lock);
+        assert!(OutputLock::acquire(&path, Duration::ZERO, &FileOptions::default()).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_no_lock_file_in_insecure_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::set_permissions(dir.path(), std::fs::Permissions::from_mode(0o777)).unwrap();
+        let path = dir.path().join(".env");
+
+        let err = OutputLock::acquire(&path, Duration::ZERO, &FileOptions::default()).err().unwrap();
+        assert!(matches!(err.downcast_ref::<PsenvError>(), Some(PsenvError::InsecureOutput(_))));
+        assert!(!dir.path().join(".env.lock").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_lock_follows_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("real.env");
+        let link = dir.path().join(".env");
+        std::fs::write(&real, "A=1\n").unwrap();
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let _lock = OutputLock::acquire(&real, Duration::ZERO, &FileOptions::default()).unwrap();
+        assert!(OutputLock::acquire(&link, Duration::ZERO, &FileOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_lock_waits_for_release() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+
+        let lock = OutputLock::acquire(&path, Duration::ZERO, &FileOptions::default()).unwrap();
+        let release = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            drop(lock);
+        });
+
+        assert!(OutputLock::acquire(&path, Duration::from_secs(5), &FileOptions::default()).is_ok());
+        release.join().unwrap();
+    }
+}
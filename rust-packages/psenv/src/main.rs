@@ -1,30 +1,72 @@
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use log::{debug, error, info, warn};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
 
 mod aws_client;
+mod backup;
+mod check;
+mod config;
+mod conflict;
+mod diagnostics;
+mod diff;
 mod env_handler;
+mod generate;
+mod interpolate;
+mod key_grammar;
+mod lint;
+mod lock;
+mod output_file;
+mod path_mapping;
+mod report;
+mod schema_loader;
 pub mod secret_masker;
+mod template_loader;
 mod template_parser;
+mod value_type;
 
 use aws_client::AwsClient;
-use env_handler::{EnvHandler, Strategy};
-use secret_masker::SecretMasker;
-use template_parser::TemplateParser;
+use check::{expected_paths, is_listed, CheckReport};
+use config::Config;
+use conflict::PromptResolver;
+use diff::DiffRenderer;
+use env_handler::{EnvHandler, EnvOptions, PruneMode, Strategy};
+use generate::TemplateGenerator;
+use interpolate::{has_references, Interpolator};
+use key_grammar::{KeyGrammar, KeyGrammarKind};
+use lint::lint_templates;
+use output_file::{parse_mode, FileOptions, Owner};
+use path_mapping::{candidate_paths, PathMapping, Precedence};
+use report::{ChangeReport, ReportFormat, RunReport};
+use secret_masker::{MaskStyle, MaskingRules, SecretMasker};
+use template_loader::{TemplateEntry, TemplateReader};
 
 #[derive(Parser)]
 #[command(name = "psenv")]
 #[command(about = "AWS Parameter Store to .env tool")]
 #[command(version)]
+#[command(args_conflicts_with_subcommands = true)]
+#[command(subcommand_negates_reqs = true)]
 struct Cli {
-    #[arg(short, long)]
-    #[arg(help = "Template file path (e.g., .env.example)")]
-    template: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    #[arg(short, long)]
-    #[arg(help = "Parameter Store prefix (must start with /)")]
-    prefix: String,
+    #[command(flatten)]
+    template_args: TemplateArgs,
+
+    #[arg(short, long, required = true)]
+    #[arg(help = "Parameter Store prefix (must start with /, repeatable for layered lookup)")]
+    prefix: Vec<String>,
+
+    #[arg(long, default_value = "last-wins")]
+    #[arg(help = "Which prefix wins when a key exists under several prefixes")]
+    precedence: Precedence,
+
+    #[arg(long)]
+    #[arg(help = "File mapping keys to parameter paths (KEY=/absolute/path or KEY=relative/path)")]
+    path_map: Option<String>,
 
     #[arg(short, long, default_value = ".env")]
     #[arg(help = "Output file (default: .env)")]
@@ -34,42 +76,287 @@ struct Cli {
     #[arg(help = "Processing strategy")]
     strategy: Strategy,
 
+    #[arg(long, default_value = "comment")]
+    #[arg(help = "What the sync strategy does with keys no longer in the template")]
+    prune_mode: PruneMode,
+
+    #[arg(long)]
+    #[arg(help = "Local-only keys the sync strategy never prunes (comma-separated)")]
+    keep_keys: Option<String>,
+
+    #[arg(long, value_parser = parse_mode)]
+    #[arg(help = "Octal mode for the output file (default: 600 for new files, existing files keep theirs)")]
+    mode: Option<u32>,
+
+    #[arg(long)]
+    #[arg(help = "Owner for the output file (user, user:group or :group)")]
+    owner: Option<Owner>,
+
+    #[arg(long, default_value = "false")]
+    #[arg(help = "Write the output file even if its directory is world-writable (sticky directories like /tmp are allowed)")]
+    allow_insecure_dir: bool,
+
+    #[arg(long, default_value = "5")]
+    #[arg(help = "Backups of the previous output file to keep (0 disables backups)")]
+    backup_retention: usize,
+
+    #[arg(long, default_value = "30")]
+    #[arg(help = "Seconds to wait for another psenv run to release the output file")]
+    lock_timeout: u64,
+
     #[arg(short, long)]
     #[arg(help = "Skip these keys (comma-separated)")]
     ignore_keys: Option<String>,
 
     #[arg(long, default_value = "false")]
-    #[arg(help = "All keys must exist, otherwise error")]
+    #[arg(help = "All keys must exist, otherwise error (keys annotated with @optional are exempt)")]
     require_all: bool,
 
-    #[arg(short, long)]
-    #[arg(help = "AWS region")]
-    region: Option<String>,
+    #[command(flatten)]
+    aws_args: AwsArgs,
 
-    #[arg(long)]
-    #[arg(help = "AWS profile")]
-    profile: Option<String>,
+    #[arg(long, default_value = "false")]
+    #[arg(help = "Leave ${KEY} references in values unexpanded")]
+    no_interpolate: bool,
 
     #[arg(long, default_value = "false")]
     #[arg(help = "Preview mode")]
     dry_run: bool,
 
-    #[arg(short, long, default_value = "false")]
+    #[arg(long)]
+    #[arg(help = "Describe the run in this format (keys, origins, changes and timings, never values)")]
+    report: Option<ReportFormat>,
+
+    #[arg(long, requires = "report")]
+    #[arg(help = "File to write the report to (default: stdout)")]
+    report_output: Option<String>,
+
+    #[arg(short, long, default_value = "false", global = true)]
     #[arg(help = "Quiet mode")]
     quiet: bool,
 
-    #[arg(short, long, default_value = "false")]
+    #[arg(short, long, default_value = "false", global = true)]
     #[arg(help = "Verbose logging")]
     verbose: bool,
 
     #[arg(long, default_value = "false")]
     #[arg(help = "Show secrets in plaintext (default: mask sensitive values)")]
     show_secrets: bool,
+
+    #[command(flatten)]
+    masking_args: MaskingArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Check templates for problems without contacting AWS")]
+    Lint(LintArgs),
+
+    #[command(about = "Compare templates with the parameters in Parameter Store")]
+    Check(CheckArgs),
+
+    #[command(about = "Restore the most recent backup of the output file")]
+    Rollback(RollbackArgs),
+
+    #[command(subcommand)]
+    #[command(about = "Work with template files")]
+    Template(TemplateCommand),
+}
+
+#[derive(Subcommand)]
+enum TemplateCommand {
+    #[command(about = "Generate a template from the parameters under a prefix")]
+    Generate(GenerateArgs),
+}
+
+#[derive(Args)]
+struct AwsArgs {
+    #[arg(short, long)]
+    #[arg(help = "AWS region")]
+    region: Option<String>,
+
+    #[arg(long)]
+    #[arg(help = "AWS profile")]
+    profile: Option<String>,
+}
+
+impl AwsArgs {
+    async fn client(&self) -> Result<AwsClient> {
+        AwsClient::new(self.region.as_deref(), self.profile.as_deref()).await
+            .with_context(|| "Failed to initialize AWS client")
+    }
+}
+
+#[derive(Args)]
+struct MaskingArgs {
+    #[arg(long)]
+    #[arg(help = "Config file with [masking] rules (default: psenv.toml if it exists)")]
+    config: Option<String>,
+
+    #[arg(long)]
+    #[arg(help = "Regex for more keys to treat as sensitive (repeatable)")]
+    mask_pattern: Vec<String>,
+
+    #[arg(long)]
+    #[arg(help = "Keys never to mask (comma-separated)")]
+    mask_allow: Option<String>,
+
+    #[arg(long)]
+    #[arg(help = "Keys always to mask (comma-separated)")]
+    mask_deny: Option<String>,
+
+    #[arg(long)]
+    #[arg(help = "How to show masked values: length (default), fixed, reveal-last[:N] or fingerprint")]
+    mask_style: Option<MaskStyle>,
+
+    #[arg(long)]
+    #[arg(help = "Salt for fingerprint masking; use the same salt to compare fingerprints across runs")]
+    mask_salt: Option<String>,
+}
+
+impl MaskingArgs {
+    /// Config file rules plus the ones given on the command line.
+    fn masker(&self) -> Result<SecretMasker> {
+        let mut rules = Config::load(self.config.as_deref())?.masking;
+        rules.extend(MaskingRules {
+            patterns: self.mask_pattern.clone(),
+            allow: split_keys(self.mask_allow.as_deref()),
+            deny: split_keys(self.mask_deny.as_deref()),
+            style: self.mask_style,
+            salt: self.mask_salt.clone(),
+        });
+
+        // Unsalted hashes of short secrets are easy to reverse with a dictionary
+        if rules.style == Some(MaskStyle::Fingerprint) && rules.salt.as_deref().is_none_or(str::is_empty) {
+            return Err(PsenvError::InvalidArguments(
+                "Fingerprint masking needs a salt, set --mask-salt or salt in the [masking] table".to_string()
+            ).into());
+        }
+
+        Ok(SecretMasker::new().with_rules(&rules)
+            .map_err(|e| PsenvError::InvalidArguments(format!("Invalid masking pattern: {}", e)))?)
+    }
+}
+
+#[derive(Args)]
+struct TemplateArgs {
+    #[arg(short, long, required = true)]
+    #[arg(help = "Template file path (e.g., .env.example or psenv.schema.yaml), repeatable")]
+    template: Vec<String>,
+
+    #[arg(long, default_value = "posix")]
+    #[arg(help = "Which names are recognized as keys in the template and output file")]
+    key_grammar: KeyGrammarKind,
+
+    #[arg(long)]
+    #[arg(help = "Custom regex keys must match (overrides --key-grammar)")]
+    key_pattern: Option<String>,
+}
+
+impl TemplateArgs {
+    fn key_grammar(&self) -> Result<KeyGrammar> {
+        match &self.key_pattern {
+            Some(pattern) => Ok(KeyGrammar::custom(pattern)
+                .map_err(|e| PsenvError::InvalidArguments(format!("{:#}", e)))?),
+            None => Ok(KeyGrammar::new(self.key_grammar)),
+        }
+    }
+}
+
+#[derive(Args)]
+struct LintArgs {
+    #[command(flatten)]
+    template_args: TemplateArgs,
+
+    #[arg(long, default_value = "false")]
+    #[arg(help = "Fail on warnings as well as errors")]
+    deny_warnings: bool,
+}
+
+#[derive(Args)]
+struct CheckArgs {
+    #[command(flatten)]
+    template_args: TemplateArgs,
+
+    #[arg(short, long, required = true)]
+    #[arg(help = "Parameter Store prefix (must start with /, repeatable for layered lookup)")]
+    prefix: Vec<String>,
+
+    #[arg(long)]
+    #[arg(help = "File mapping keys to parameter paths (KEY=/absolute/path or KEY=relative/path)")]
+    path_map: Option<String>,
+
+    #[arg(short, long)]
+    #[arg(help = "Skip these keys (comma-separated)")]
+    ignore_keys: Option<String>,
+
+    #[arg(long, default_value = "false")]
+    #[arg(help = "All keys must exist, otherwise error (keys annotated with @optional are exempt)")]
+    require_all: bool,
+
+    #[arg(long, default_value = "false")]
+    #[arg(help = "Report parameters no template refers to without failing")]
+    allow_orphans: bool,
+
+    #[command(flatten)]
+    aws_args: AwsArgs,
+}
+
+#[derive(Args)]
+struct RollbackArgs {
+    #[arg(short, long, default_value = ".env")]
+    #[arg(help = "Output file whose backup to restore (default: .env)")]
+    output: String,
+
+    #[arg(long, default_value = "false")]
+    #[arg(help = "Undo the last rollback, restoring the file it replaced")]
+    undo: bool,
+
+    #[arg(long, default_value = "5")]
+    #[arg(help = "Copies of replaced files to keep (at least 1)")]
+    backup_retention: usize,
+
+    #[arg(long, default_value = "false")]
+    #[arg(help = "Write the output file even if its directory is world-writable (sticky directories like /tmp are allowed)")]
+    allow_insecure_dir: bool,
+
+    #[arg(long, default_value = "30")]
+    #[arg(help = "Seconds to wait for another psenv run to release the output file")]
+    lock_timeout: u64,
+}
+
+#[derive(Args)]
+struct GenerateArgs {
+    #[arg(short, long)]
+    #[arg(help = "Parameter Store prefix to list (must start with /)")]
+    prefix: String,
+
+    #[arg(short, long, default_value = ".env.example")]
+    #[arg(help = "Output file, or - for stdout (default: .env.example)")]
+    output: String,
+
+    #[arg(long, default_value = "")]
+    #[arg(help = "Value written for secure strings and sensitive keys")]
+    placeholder: String,
+
+    #[arg(long, default_value = "posix")]
+    #[arg(help = "Which parameter names can be used as keys as they are")]
+    key_grammar: KeyGrammarKind,
+
+    #[arg(long, default_value = "false")]
+    #[arg(help = "Overwrite the output file if it exists")]
+    force: bool,
+
+    #[command(flatten)]
+    aws_args: AwsArgs,
+
+    #[command(flatten)]
+    masking_args: MaskingArgs,
 }
 
 #[tokio::main]
 async fn main() {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
 
     // Initialize logging
     let log_level = if cli.verbose {
@@ -82,12 +369,26 @@ async fn main() {
 
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level)).init();
 
-    if let Err(e) = run(cli).await {
-        error!("Error: {}", e);
+    let result = match cli.command.take() {
+        Some(Command::Lint(args)) => run_lint(args),
+        Some(Command::Check(args)) => run_check(args).await,
+        // Waiting for the output lock blocks the thread
+        Some(Command::Rollback(args)) => tokio::task::block_in_place(|| run_rollback(args)),
+        Some(Command::Template(TemplateCommand::Generate(args))) => run_generate(args).await,
+        None => run(cli).await,
+    };
+
+    if let Err(e) = result {
+        error!("Error: {:#}", e);
         let exit_code = match e.downcast_ref::<PsenvError>() {
             Some(PsenvError::InvalidArguments(_)) => 1,
             Some(PsenvError::RequiredParameterMissing(_)) => 3,
             Some(PsenvError::FileExists(_)) => 4,
+            Some(PsenvError::InvalidValue(_)) => 5,
+            Some(PsenvError::LintFailed(_)) => 6,
+            Some(PsenvError::CheckFailed(_)) => 7,
+            Some(PsenvError::InsecureOutput(_)) => 8,
+            Some(PsenvError::Locked(_)) => 9,
             _ => 1,
         };
         std::process::exit(exit_code);
@@ -95,97 +396,278 @@ async fn main() {
 }
 
 async fn run(cli: Cli) -> Result<()> {
-    // Validate prefix
-    if !cli.prefix.starts_with('/') {
+    let Some(format) = cli.report else {
+        return sync(&cli, &mut RunReport::default()).await;
+    };
+
+    // The preview already goes to stdout
+    if cli.dry_run && cli.report_output.is_none() {
+        return Err(PsenvError::InvalidArguments(
+            "--report with --dry-run needs --report-output".to_string()
+        ).into());
+    }
+
+    let start = Instant::now();
+    let mut report = RunReport {
+        templates: cli.template_args.template.clone(),
+        prefixes: cli.prefix.clone(),
+        output: cli.output.clone(),
+        strategy: strategy_name(&cli.strategy),
+        dry_run: cli.dry_run,
+        ..RunReport::default()
+    };
+
+    let result = sync(&cli, &mut report).await;
+    report.finish(&result, start);
+
+    // A failed run still gets a report; its error wins over a failure to write it
+    let written = report.write(format, cli.report_output.as_deref());
+    result.and(written)
+}
+
+async fn sync(cli: &Cli, report: &mut RunReport) -> Result<()> {
+    // Validate prefixes
+    if cli.prefix.iter().any(|prefix| !prefix.starts_with('/')) {
         return Err(PsenvError::InvalidArguments("Prefix must start with '/'".to_string()).into());
     }
 
-    debug!("Starting psenv with template: {}, prefix: {}, output: {}",
-           cli.template, cli.prefix, cli.output);
+    debug!("Starting psenv with templates: {:?}, prefixes: {:?} ({:?}), output: {}",
+           cli.template_args.template, cli.prefix, cli.precedence, cli.output);
 
     // Parse ignore keys
-    let ignore_keys: Vec<String> = cli.ignore_keys
-        .as_deref()
-        .unwrap_or("")
-        .split(',')
-        .filter(|s| !s.trim().is_empty())
-        .map(|s| s.trim().to_string())
-        .collect();
+    let ignore_keys = split_keys(cli.ignore_keys.as_deref());
 
     debug!("Ignore keys: {:?}", ignore_keys);
 
+    let key_grammar = cli.template_args.key_grammar()?;
+    let mut masker = cli.masking_args.masker()?;
+    let templates = &cli.template_args.template;
+
     // Parse template file
-    let parser = TemplateParser::new();
-    let keys = parser.parse_template(&cli.template)
-        .with_context(|| format!("Failed to parse template file: {}", cli.template))?;
+    let started = Instant::now();
+    let reader = TemplateReader::with_key_grammar(key_grammar.clone());
+    let entries = reader.parse_entries(templates)
+        .with_context(|| format!("Failed to parse template file: {}", templates.join(", ")))?;
+
+    report.time("templates", started);
 
-    info!("Found {} keys in template", keys.len());
+    info!("Found {} keys in template", entries.len());
+
+    // Ignored keys are still template keys, so sync leaves them alone
+    let template_keys: HashSet<String> = entries.iter().map(|entry| entry.key.clone()).collect();
 
     // Filter out ignored keys
-    let filtered_keys: Vec<String> = keys.into_iter()
-        .filter(|key| !ignore_keys.contains(key))
-        .collect();
+    let (filtered_entries, ignored_entries): (Vec<TemplateEntry>, Vec<TemplateEntry>) = entries.into_iter()
+        .partition(|entry| !ignore_keys.contains(&entry.key));
 
-    info!("Processing {} keys after filtering", filtered_keys.len());
+    report.keys.requested = filtered_entries.iter().map(|entry| entry.key.clone()).collect();
+    report.keys.ignored = ignored_entries.into_iter().map(|entry| entry.key).collect();
+
+    info!("Processing {} keys after filtering", filtered_entries.len());
+
+    let path_mapping = match &cli.path_map {
+        Some(path) => PathMapping::load(path)?,
+        None => PathMapping::default(),
+    };
 
     // Initialize AWS client
-    let aws_client = AwsClient::new(cli.region.as_deref(), cli.profile.as_deref()).await
-        .with_context(|| "Failed to initialize AWS client")?;
+    let aws_client = cli.aws_args.client().await?;
 
     // Fetch parameters from AWS Parameter Store
     let mut values = HashMap::new();
+    let mut origins = HashMap::new();
     let mut missing_keys = Vec::new();
+    let mut errored_keys = Vec::new();
+    let mut secure_keys = HashSet::new();
+    let started = Instant::now();
+
+    'keys: for entry in &filtered_entries {
+        let key = &entry.key;
+        // A mapping file entry takes precedence over an @path annotation
+        let path_override = path_mapping.get(key).or(entry.path.as_deref());
+
+        for param_path in candidate_paths(&cli.prefix, cli.precedence, key, path_override) {
+            debug!("Fetching parameter: {}", param_path);
+
+            match aws_client.get_parameter(&param_path).await {
+                Ok(Some(parameter)) => {
+                    debug!(
+                        "Retrieved value for key: {} from {} (version {}, type {}, last modified {}, ARN {})",
+                        key,
+                        param_path,
+                        parameter.version,
+                        parameter.parameter_type.as_ref().map_or("unknown", |t| t.as_str()),
+                        parameter.last_modified.map_or_else(|| "unknown".to_string(), |date| date.to_string()),
+                        parameter.arn.as_deref().unwrap_or("unknown")
+                    );
+                    if parameter.is_secure() {
+                        masker.mark_sensitive(key);
+                        secure_keys.insert(key.clone());
+                    }
+                    values.insert(key.clone(), parameter.value);
+                    origins.insert(key.clone(), param_path);
+                    report.keys.found.push(key.clone());
+                    continue 'keys;
+                }
+                Ok(None) => {
+                    debug!("Parameter not found: {}", param_path);
+                }
+                Err(e) => {
+                    // Don't fall through to a lower-precedence layer on errors
+                    error!("Failed to retrieve parameter {}: {}", param_path, e);
+                    missing_keys.push(key.clone());
+                    errored_keys.push(key.clone());
+                    continue 'keys;
+                }
+            }
+        }
 
-    for key in &filtered_keys {
-        let param_path = format!("{}{}", cli.prefix, key);
-        debug!("Fetching parameter: {}", param_path);
+        warn!("Parameter not found for key: {}", key);
+        missing_keys.push(key.clone());
+    }
 
-        match aws_client.get_parameter(&param_path).await {
-            Ok(Some(value)) => {
-                values.insert(key.clone(), value);
-                debug!("Retrieved value for key: {}", key);
-            }
-            Ok(None) => {
-                warn!("Parameter not found: {}", param_path);
-                missing_keys.push(key.clone());
-            }
-            Err(e) => {
-                error!("Failed to retrieve parameter {}: {}", param_path, e);
-                missing_keys.push(key.clone());
+    report.time("fetch", started);
+    report.keys.errored = errored_keys.clone();
+
+    if !cli.no_interpolate {
+        let defaults: HashMap<String, String> = filtered_entries.iter()
+            .filter_map(|entry| entry.default.clone().map(|default| (entry.key.clone(), default)))
+            .collect();
+
+        // Keys missing from Parameter Store can be composed from other values by their template default,
+        // but a failed lookup must not be papered over
+        for entry in &filtered_entries {
+            if let Some(default) = entry.default.as_ref().filter(|d| has_references(d)) {
+                if missing_keys.contains(&entry.key) && !errored_keys.contains(&entry.key) {
+                    debug!("Composing {} from its template default", entry.key);
+                    values.insert(entry.key.clone(), default.clone());
+                    origins.insert(entry.key.clone(), "template default".to_string());
+                    missing_keys.retain(|key| key != &entry.key);
+                }
             }
         }
+
+        // SecureStrings are written exactly as stored, whatever characters they contain
+        values = Interpolator::new(&values, &defaults)
+            .with_literal_keys(secure_keys)
+            .resolve_all()
+            .map_err(|e| PsenvError::InvalidValue(format!("Failed to resolve references: {}", e)))?;
     }
 
+    // Errored keys count as missing for the required check, but the report keeps them apart
+    report.keys.missing = missing_keys.iter().filter(|key| !errored_keys.contains(key)).cloned().collect();
+    report.origins = origins.iter().map(|(key, origin)| (key.clone(), origin.clone())).collect();
+
     // Check if all required parameters are present
-    if cli.require_all && !missing_keys.is_empty() {
+    let missing_required: Vec<String> = filtered_entries.iter()
+        .filter(|entry| entry.is_required(cli.require_all) && missing_keys.contains(&entry.key))
+        .map(|entry| match &entry.description {
+            Some(description) => format!("{} ({})", entry.key, description),
+            None => entry.key.clone(),
+        })
+        .collect();
+
+    if !missing_required.is_empty() {
         return Err(PsenvError::RequiredParameterMissing(
-            format!("Missing required parameters: {}", missing_keys.join(", "))
+            format!("Missing required parameters: {}", missing_required.join(", "))
         ).into());
     }
 
-    info!("Retrieved {} out of {} parameters", values.len(), filtered_keys.len());
+    info!("Retrieved {} out of {} parameters", values.len(), filtered_entries.len());
 
     if !missing_keys.is_empty() {
         warn!("Missing parameters: {}", missing_keys.join(", "));
     }
 
+    // Validate values against their declared types before anything is written
+    let mut invalid_values = Vec::new();
+    for entry in &filtered_entries {
+        if let (Some(value_type), Some(value)) = (&entry.value_type, values.get(&entry.key)) {
+            if let Err(reason) = value_type.validate(value) {
+                invalid_values.push(format!("{} ({}: {})", entry.key, value_type, reason));
+            }
+        }
+    }
+
+    if !invalid_values.is_empty() {
+        return Err(PsenvError::InvalidValue(
+            format!("Values do not match their declared types: {}", invalid_values.join("; "))
+        ).into());
+    }
+
+    for entry in filtered_entries.iter().filter(|entry| entry.sensitive) {
+        masker.mark_sensitive(&entry.key);
+    }
+
+    // Prompts need someone to answer them, e.g. not in CI
+    let strategy = match cli.strategy.clone() {
+        Strategy::Interactive if !std::io::stdin().is_terminal() => {
+            warn!("stdin is not a terminal, keeping local values instead of asking about conflicts");
+            Strategy::KeepLocal
+        }
+        strategy => strategy,
+    };
+    report.strategy = strategy_name(&strategy);
+
     // Handle .env file generation
-    let env_handler = EnvHandler::new();
+    let env_handler = EnvHandler::with_options(EnvOptions {
+        key_grammar: key_grammar.clone(),
+        template_keys,
+        keep_keys: split_keys(cli.keep_keys.as_deref()).into_iter().collect(),
+        prune_mode: cli.prune_mode,
+        file: FileOptions {
+            mode: cli.mode,
+            owner: cli.owner.clone(),
+            allow_insecure_dir: cli.allow_insecure_dir,
+        },
+        backup_retention: cli.backup_retention,
+        lock_timeout: Duration::from_secs(cli.lock_timeout),
+    });
 
     if cli.dry_run {
         info!("Dry run mode - would write to: {}", cli.output);
-        let masker = SecretMasker::new();
         let mut sorted_keys: Vec<&String> = values.keys().collect();
         sorted_keys.sort();
 
         for key in sorted_keys {
-            if let Some(value) = values.get(key) {
-                println!("{}", masker.format_output(key, value, cli.show_secrets));
+            if let (Some(value), Some(origin)) = (values.get(key), origins.get(key)) {
+                println!("{}  # from {}", masker.format_output(key, value, cli.show_secrets), origin);
             }
         }
+
+        // Show what the chosen strategy would do to the file
+        let started = Instant::now();
+        let plan = env_handler.plan(&cli.output, &values, &strategy)?;
+        report.time("write", started);
+        report.changes = Some(ChangeReport::from(&plan.summary));
+        let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        let diff = DiffRenderer::new(&masker, &key_grammar, cli.show_secrets, color)
+            .render(&cli.output, plan.existing.as_deref(), &plan.content);
+
+        if diff.is_empty() {
+            info!("No changes to {}", cli.output);
+        } else {
+            println!();
+            print!("{}", diff);
+            info!(
+                "Would update {} variables, add {} variables and prune {} variables",
+                plan.summary.updated.len(),
+                plan.summary.added.len(),
+                plan.summary.pruned.len()
+            );
+        }
     } else {
-        env_handler.handle_env_file(&cli.output, &values, cli.strategy)
+        let env_handler = match strategy {
+            Strategy::Interactive => env_handler.with_resolver(Box::new(PromptResolver::stdio(masker, cli.show_secrets))),
+            _ => env_handler,
+        };
+
+        let started = Instant::now();
+        // Waiting for the output lock and for answers to prompts blocks the thread
+        let summary = tokio::task::block_in_place(|| env_handler.handle_env_file(&cli.output, &values, strategy))
             .with_context(|| format!("Failed to handle .env file: {}", cli.output))?;
+        report.time("write", started);
+        report.changes = Some(ChangeReport::from(&summary));
 
         info!("Successfully updated {}", cli.output);
     }
@@ -193,6 +675,145 @@ async fn run(cli: Cli) -> Result<()> {
     Ok(())
 }
 
+fn strategy_name(strategy: &Strategy) -> String {
+    strategy.to_possible_value()
+        .map_or_else(|| format!("{:?}", strategy), |value| value.get_name().to_string())
+}
+
+/// Splits a comma-separated key list, ignoring blanks.
+fn split_keys(list: Option<&str>) -> Vec<String> {
+    list.unwrap_or("")
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| s.trim().to_string())
+        .collect()
+}
+
+fn run_lint(args: LintArgs) -> Result<()> {
+    let reader = TemplateReader::with_key_grammar(args.template_args.key_grammar()?);
+    lint_templates(&reader, &args.template_args.template, args.deny_warnings)
+}
+
+async fn run_check(args: CheckArgs) -> Result<()> {
+    if args.prefix.iter().any(|prefix| !prefix.starts_with('/')) {
+        return Err(PsenvError::InvalidArguments("Prefix must start with '/'".to_string()).into());
+    }
+
+    let templates = &args.template_args.template;
+    let reader = TemplateReader::with_key_grammar(args.template_args.key_grammar()?);
+    let entries = reader.parse_entries(templates)
+        .with_context(|| format!("Failed to parse template file: {}", templates.join(", ")))?;
+
+    let path_mapping = match &args.path_map {
+        Some(path) => PathMapping::load(path)?,
+        None => PathMapping::default(),
+    };
+
+    let ignore_keys = split_keys(args.ignore_keys.as_deref());
+    let aws_client = args.aws_args.client().await?;
+
+    // Only names are needed, so skip fetching values
+    let mut listed = Vec::new();
+    for prefix in &args.prefix {
+        let parameters = aws_client.describe_parameters(prefix).await?;
+        listed.extend(parameters.into_iter().map(|parameter| parameter.name));
+    }
+
+    let mut existing: HashSet<String> = listed.iter().cloned().collect();
+
+    // Paths outside the listed prefixes (absolute or nested @path overrides) are looked up one by one
+    for entry in entries.iter().filter(|entry| !ignore_keys.contains(&entry.key)) {
+        for path in expected_paths(entry, &args.prefix, &path_mapping) {
+            if !is_listed(&path, &args.prefix) && !existing.contains(&path) {
+                debug!("Looking up parameter outside the prefixes: {}", path);
+                if aws_client.parameter_exists(&path).await? {
+                    existing.insert(path);
+                }
+            }
+        }
+    }
+
+    let report = CheckReport::build(&entries, &args.prefix, &path_mapping, &existing, &listed, &ignore_keys, args.require_all);
+    print!("{}", report.render());
+
+    let missing_required = report.missing_required();
+    let orphans = if args.allow_orphans { 0 } else { report.orphans.len() };
+
+    if missing_required > 0 || orphans > 0 {
+        return Err(PsenvError::CheckFailed(format!(
+            "{} missing key(s), {} orphan parameter(s)",
+            missing_required,
+            report.orphans.len()
+        ))
+        .into());
+    }
+
+    info!(
+        "Checked {} keys against {}: {} missing optional key(s), {} orphan parameter(s)",
+        entries.len(),
+        args.prefix.join(", "),
+        report.missing.len(),
+        report.orphans.len()
+    );
+
+    Ok(())
+}
+
+fn run_rollback(args: RollbackArgs) -> Result<()> {
+    let options = FileOptions {
+        allow_insecure_dir: args.allow_insecure_dir,
+        ..FileOptions::default()
+    };
+
+    let lock_timeout = Duration::from_secs(args.lock_timeout);
+    let path = std::path::Path::new(&args.output);
+    let restored = if args.undo {
+        backup::undo_rollback(path, args.backup_retention, &options, lock_timeout)
+            .with_context(|| format!("Failed to undo the rollback of {}", args.output))?
+    } else {
+        backup::rollback(path, args.backup_retention, &options, lock_timeout)
+            .with_context(|| format!("Failed to roll back {}", args.output))?
+    };
+
+    info!("Restored {} from {}", args.output, restored.display());
+    Ok(())
+}
+
+async fn run_generate(args: GenerateArgs) -> Result<()> {
+    if !args.prefix.starts_with('/') {
+        return Err(PsenvError::InvalidArguments("Prefix must start with '/'".to_string()).into());
+    }
+
+    let to_stdout = args.output == "-";
+    if !to_stdout && !args.force && std::path::Path::new(&args.output).exists() {
+        return Err(PsenvError::FileExists(
+            format!("{} already exists, use --force to overwrite it", args.output)
+        ).into());
+    }
+
+    let masker = args.masking_args.masker()?;
+    let aws_client = args.aws_args.client().await?;
+    let parameters = aws_client.list_parameters(&args.prefix).await?;
+
+    if parameters.is_empty() {
+        warn!("No parameters found under {}", args.prefix);
+    }
+
+    let generator = TemplateGenerator::new(KeyGrammar::new(args.key_grammar), &args.placeholder)
+        .with_masker(masker);
+    let template = generator.render(&args.prefix, &parameters);
+
+    if to_stdout {
+        print!("{}", template);
+    } else {
+        std::fs::write(&args.output, template)
+            .with_context(|| format!("Failed to write template file: {}", args.output))?;
+        info!("Wrote {} keys from {} to {}", parameters.len(), args.prefix, args.output);
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, thiserror::Error)]
 enum PsenvError {
     #[error("Invalid arguments: {0}")]
@@ -203,4 +824,19 @@ enum PsenvError {
 
     #[error("File exists: {0}")]
     FileExists(String),
+
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+
+    #[error("Lint failed: {0}")]
+    LintFailed(String),
+
+    #[error("Check failed: {0}")]
+    CheckFailed(String),
+
+    #[error("Insecure output: {0}")]
+    InsecureOutput(String),
+
+    #[error("Output file locked: {0}")]
+    Locked(String),
 }
\ No newline at end of file
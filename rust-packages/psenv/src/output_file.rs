@@ -0,0 +1,384 @@
+use anyhow::{anyhow, Context, Result};
+use log::{debug, warn};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tempfile::NamedTempFile;
+
+use crate::PsenvError;
+
+/// Mode for files we create; they usually hold secrets.
+pub const DEFAULT_MODE: u32 = 0o600;
+
+/// Permissions and ownership for written files.
+#[derive(Debug, Clone, Default)]
+pub struct FileOptions {
+    /// Applied to new and existing files; when unset, new files get
+    /// [`DEFAULT_MODE`] and existing ones keep their mode.
+    pub mode: Option<u32>,
+    pub owner: Option<Owner>,
+    /// Write even when the directory is world-writable.
+    pub allow_insecure_dir: bool,
+}
+
+/// A `user[:group]` to hand written files to, by name or numeric id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Owner {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl FromStr for Owner {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (user, group) = match s.split_once(':') {
+            Some((user, group)) => (user, Some(group)),
+            None => (s, None),
+        };
+
+        let owner = Owner {
+            uid: Some(user).filter(|u| !u.is_empty()).map(lookup_user).transpose()?,
+            gid: group.filter(|g| !g.is_empty()).map(lookup_group).transpose()?,
+        };
+
+        if owner.uid.is_none() && owner.gid.is_none() {
+            return Err(anyhow!("expected user, user:group or :group, got `{}`", s));
+        }
+
+        Ok(owner)
+    }
+}
+
+#[cfg(unix)]
+fn lookup_user(name: &str) -> Result<u32> {
+    if let Ok(uid) = name.parse() {
+        return Ok(uid);
+    }
+    nix::unistd::User::from_name(name)?
+        .map(|user| user.uid.as_raw())
+        .ok_or_else(|| anyhow!("unknown user `{}`", name))
+}
+
+#[cfg(unix)]
+fn lookup_group(name: &str) -> Result<u32> {
+    if let Ok(gid) = name.parse() {
+        return Ok(gid);
+    }
+    nix::unistd::Group::from_name(name)?
+        .map(|group| group.gid.as_raw())
+        .ok_or_else(|| anyhow!("unknown group `{}`", name))
+}
+
+#[cfg(not(unix))]
+fn lookup_user(_name: &str) -> Result<u32> {
+    Err(anyhow!("setting the owner is only supported on Unix"))
+}
+
+#[cfg(not(unix))]
+fn lookup_group(_name: &str) -> Result<u32> {
+    Err(anyhow!("setting the owner is only supported on Unix"))
+}
+
+/// Parses an octal mode such as `600`, `0640` or `0o640`.
+pub fn parse_mode(s: &str) -> Result<u32, String> {
+    let digits = s.strip_prefix("0o").unwrap_or(s);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("expected an octal mode such as 600 or 0640, got `{}`", s)),
+    }
+}
+
+/// Replaces `path` with `content` so that readers see either the old or the new file.
+///
+/// The content goes to a temp file next to the target, which is synced and
+/// then renamed over it. An existing target keeps its permissions and, where
+/// we're allowed to set it, its ownership, unless `options` say otherwise.
+/// Symlinks are followed.
+pub fn write_atomic(path: &Path, content: &str, options: &FileOptions) -> Result<()> {
+    let target = resolve_target(path)?;
+    let dir = writable_dir(path, &target, options)?;
+
+    let file_name = target.file_name()
+        .with_context(|| format!("Not a file path: {}", path.display()))?
+        .to_string_lossy();
+
+    // A random name, so a temp file left behind by a crashed run never gets in the way.
+    // It is created with mode 0600 and removed again if anything below fails.
+    let mut temp = tempfile::Builder::new()
+        .prefix(&format!(".{}.", file_name))
+        .suffix(".psenv.tmp")
+        .tempfile_in(&dir)
+        .with_context(|| format!("Failed to create temp file in {}", dir.display()))?;
+
+    debug!("Writing {} through {}", target.display(), temp.path().display());
+
+    write_temp(&mut temp, &target, content, options)?;
+    temp.persist(&target)
+        .map_err(|e| e.error)
+        .with_context(|| format!("Failed to replace {}", target.display()))?;
+
+    // Make the rename itself durable; not every platform can sync a directory
+    if let Ok(dir) = File::open(&dir) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+/// The directory `target`, the resolved `path`, lives in. It is refused when
+/// any user could replace files there, unless `options` allow it.
+pub fn writable_dir(path: &Path, target: &Path, options: &FileOptions) -> Result<PathBuf> {
+    let dir = match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+
+    if !options.allow_insecure_dir && is_world_writable(&dir) {
+        return Err(PsenvError::InsecureOutput(format!(
+            "{} is in {}, where any user can replace or remove files, use --allow-insecure-dir to write there anyway",
+            path.display(),
+            dir.display()
+        ))
+        .into());
+    }
+
+    Ok(dir)
+}
+
+fn write_temp(temp: &mut NamedTempFile, target: &Path, content: &str, options: &FileOptions) -> Result<()> {
+    let temp_path = temp.path().to_path_buf();
+    let file = temp.as_file_mut();
+
+    file.write_all(content.as_bytes())
+        .with_context(|| format!("Failed to write temp file: {}", temp_path.display()))?;
+
+    let existing = fs::metadata(target).ok();
+
+    let permissions = match (options.mode, &existing) {
+        (Some(mode), _) => permissions_from_mode(mode),
+        (None, Some(metadata)) => {
+            if let Some(mode) = exposed_mode(metadata) {
+                warn!(
+                    "{} can be read by other users (mode {:o}), use --mode 600 to restrict it",
+                    target.display(),
+                    mode
+                );
+            }
+            Some(metadata.permissions())
+        }
+        (None, None) => None,
+    };
+    if let Some(permissions) = permissions {
+        file.set_permissions(permissions)
+            .with_context(|| format!("Failed to set permissions on {}", temp_path.display()))?;
+    }
+
+    match (&options.owner, &existing) {
+        (Some(owner), _) => set_owner(&temp_path, owner)?,
+        (None, Some(metadata)) => copy_ownership(&temp_path, metadata),
+        (None, None) => {}
+    }
+
+    file.sync_all()
+        .with_context(|| format!("Failed to sync temp file: {}", temp_path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_ownership(temp_path: &Path, metadata: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+
+    // Only root can give a file away, so a failure here is expected for other users' files
+    if let Err(e) = std::os::unix::fs::chown(temp_path, Some(metadata.uid()), Some(metadata.gid())) {
+        debug!("Could not keep ownership {}:{}: {}", metadata.uid(), metadata.gid(), e);
+    }
+}
+
+#[cfg(unix)]
+fn set_owner(path: &Path, owner: &Owner) -> Result<()> {
+    std::os::unix::fs::chown(path, owner.uid, owner.gid)
+        .with_context(|| format!("Failed to change the owner of {}", path.display()))
+}
+
+#[cfg(unix)]
+fn permissions_from_mode(mode: u32) -> Option<fs::Permissions> {
+    Some(std::os::unix::fs::PermissionsExt::from_mode(mode))
+}
+
+/// The mode of a file that others than its owner can read.
+#[cfg(unix)]
+fn exposed_mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode() & 0o7777;
+    (mode & 0o044 != 0).then_some(mode)
+}
+
+/// World-writable without the sticky bit, so anyone can replace our files.
+/// In sticky directories like `/tmp`, only a file's owner can rename or delete it.
+#[cfg(unix)]
+fn is_world_writable(dir: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(dir).is_ok_and(|metadata| {
+        let mode = metadata.permissions().mode();
+        mode & 0o002 != 0 && mode & 0o1000 == 0
+    })
+}
+
+#[cfg(not(unix))]
+fn copy_ownership(_temp_path: &Path, _metadata: &fs::Metadata) {}
+
+#[cfg(not(unix))]
+fn set_owner(_path: &Path, _owner: &Owner) -> Result<()> {
+    Err(anyhow!("setting the owner is only supported on Unix"))
+}
+
+/// Unix modes have no equivalent here, so `--mode` is ignored.
+#[cfg(not(unix))]
+fn permissions_from_mode(_mode: u32) -> Option<fs::Permissions> {
+    None
+}
+
+#[cfg(not(unix))]
+fn exposed_mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(not(unix))]
+fn is_world_writable(_dir: &Path) -> bool {
+    false
+}
+
+/// Writes through symlinks instead of replacing the link with a regular file.
+pub fn resolve_target(path: &Path) -> Result<PathBuf> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => fs::canonicalize(path)
+            .with_context(|| format!("Failed to resolve symlink: {}", path.display())),
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+
+        write_atomic(&path, "A=1\n", &FileOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "A=1\n");
+
+        write_atomic(&path, "A=2\n", &FileOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "A=2\n");
+
+        // No temp files are left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_write_atomic_ignores_stale_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        let stale = dir.path().join(format!(".env.psenv-{}.tmp", std::process::id()));
+        fs::write(&stale, "left by a crashed run").unwrap();
+
+        write_atomic(&path, "A=1\n", &FileOptions::default()).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "A=1\n");
+        assert_eq!(fs::read_to_string(&stale).unwrap(), "left by a crashed run");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_keeps_permissions_and_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("real.env");
+        let link = dir.path().join(".env");
+        fs::write(&real, "A=1\n").unwrap();
+        fs::set_permissions(&real, fs::Permissions::from_mode(0o640)).unwrap();
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        write_atomic(&link, "A=2\n", &FileOptions::default()).unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&real).unwrap(), "A=2\n");
+        assert_eq!(fs::metadata(&real).unwrap().permissions().mode() & 0o777, 0o640);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        write_atomic(&path, "A=1\n", &FileOptions::default()).unwrap();
+        assert_eq!(mode(&path), 0o600);
+
+        let options = FileOptions { mode: Some(0o640), ..FileOptions::default() };
+        write_atomic(&path, "A=2\n", &options).unwrap();
+        assert_eq!(mode(&path), 0o640);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_refuse_world_writable_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o777)).unwrap();
+        let path = dir.path().join(".env");
+
+        let err = write_atomic(&path, "A=1\n", &FileOptions::default()).unwrap_err();
+        assert!(matches!(err.downcast_ref::<PsenvError>(), Some(PsenvError::InsecureOutput(_))));
+        assert!(!path.exists());
+
+        let options = FileOptions { allow_insecure_dir: true, ..FileOptions::default() };
+        write_atomic(&path, "A=1\n", &options).unwrap();
+        assert!(path.exists());
+
+        // Sticky directories like /tmp only let owners replace their files
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o1777)).unwrap();
+        write_atomic(&path, "A=2\n", &FileOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "A=2\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exposed_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mode = |mode| {
+            fs::set_permissions(file.path(), fs::Permissions::from_mode(mode)).unwrap();
+            exposed_mode(&fs::metadata(file.path()).unwrap())
+        };
+
+        assert_eq!(mode(0o644), Some(0o644));
+        assert_eq!(mode(0o640), Some(0o640));
+        assert_eq!(mode(0o600), None);
+    }
+
+    #[test]
+    fn test_parse_mode_and_owner() {
+        assert_eq!(parse_mode("600"), Ok(0o600));
+        assert_eq!(parse_mode("0640"), Ok(0o640));
+        assert_eq!(parse_mode("0o755"), Ok(0o755));
+        assert!(parse_mode("800").is_err());
+        assert!(parse_mode("rw-").is_err());
+
+        assert_eq!("1000:1001".parse::<Owner>().unwrap(), Owner { uid: Some(1000), gid: Some(1001) });
+        assert_eq!(":50".parse::<Owner>().unwrap(), Owner { uid: None, gid: Some(50) });
+        assert!(":".parse::<Owner>().is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use log::debug;
+use std::collections::HashMap;
+use std::fs;
+
+/// Per-key parameter paths loaded from a mapping file.
+///
+/// Each non-comment line has the form `KEY=/absolute/path` or `KEY=relative/path`:
+///
+/// ```text
+/// # Shared values
+/// DATABASE_URL=/shared/prod/db/url
+/// # Resolved against the prefix
+/// SMTP_PASSWORD=mail/password
+/// ```
+#[derive(Debug, Default)]
+pub struct PathMapping {
+    paths: HashMap<String, String>,
+}
+
+impl PathMapping {
+    pub fn load(mapping_path: &str) -> Result<Self> {
+        debug!("Loading path mapping file: {}", mapping_path);
+
+        let content = fs::read_to_string(mapping_path)
+            .with_context(|| format!("Failed to read path mapping file: {}", mapping_path))?;
+
+        Self::parse(&content).with_context(|| format!("Invalid path mapping file: {}", mapping_path))
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut paths = HashMap::new();
+
+        for (line_num, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            let (key, path) = trimmed
+                .split_once('=')
+                .map(|(key, path)| (key.trim(), path.trim()))
+                .filter(|(key, path)| !key.is_empty() && !path.is_empty())
+                .ok_or_else(|| anyhow!("line {}: expected KEY=/parameter/path", line_num + 1))?;
+
+            if paths.insert(key.to_string(), path.to_string()).is_some() {
+                return Err(anyhow!("line {}: duplicate mapping for {}", line_num + 1, key));
+            }
+        }
+
+        debug!("Loaded {} path mappings", paths.len());
+
+        Ok(PathMapping { paths })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.paths.get(key).map(String::as_str)
+    }
+}
+
+/// Builds the Parameter Store path for `key`.
+///
+/// `path_override` comes from a mapping file or an `@path` annotation. Absolute
+/// overrides are used as-is, relative ones are appended to the prefix.
+pub fn parameter_path(prefix: &str, key: &str, path_override: Option<&str>) -> String {
+    match path_override {
+        Some(path) if path.starts_with('/') => path.to_string(),
+        Some(path) => format!("{}{}", prefix, path),
+        None => format!("{}{}", prefix, key),
+    }
+}
+
+/// Which prefix wins when a key exists under several `--prefix` layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Precedence {
+    #[value(name = "last-wins")]
+    LastWins,
+    #[value(name = "first-wins")]
+    FirstWins,
+}
+
+/// Returns the paths to try for `key`, highest precedence first.
+pub fn candidate_paths(
+    prefixes: &[String],
+    precedence: Precedence,
+    key: &str,
+    path_override: Option<&str>,
+) -> Vec<String> {
+    if let Some(path) = path_override.filter(|path| path.starts_with('/')) {
+        return vec![path.to_string()];
+    }
+
+    let paths = prefixes
+        .iter()
+        .map(|prefix| parameter_path(prefix, key, path_override));
+
+    match precedence {
+        Precedence::LastWins => paths.rev().collect(),
+        Precedence::FirstWins => paths.collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mapping() {
+        let mapping = PathMapping::parse(
+            "# comment\n\nDATABASE_URL=/shared/prod/db/url\nSMTP_PASSWORD = mail/password\n",
+        )
+        .unwrap();
+
+        assert_eq!(mapping.get("DATABASE_URL"), Some("/shared/prod/db/url"));
+        assert_eq!(mapping.get("SMTP_PASSWORD"), Some("mail/password"));
+        assert_eq!(mapping.get("OTHER"), None);
+
+        assert!(PathMapping::parse("DATABASE_URL\n").is_err());
+        assert!(PathMapping::parse("A=/x\nA=/y\n").is_err());
+    }
+
+    #[test]
+    fn test_parameter_path() {
+        assert_eq!(parameter_path("/svc-a/prod/", "DB_HOST", None), "/svc-a/prod/DB_HOST");
+        assert_eq!(
+            parameter_path("/svc-a/prod/", "DATABASE_URL", Some("/shared/prod/db/url")),
+            "/shared/prod/db/url"
+        );
+        assert_eq!(
+            parameter_path("/svc-a/prod/", "SMTP_PASSWORD", Some("mail/password")),
+            "/svc-a/prod/mail/password"
+        );
+    }
+
+    #[test]
+    fn test_candidate_paths() {
+        let prefixes = vec!["/shared/".to_string(), "/studio/".to_string(), "/studio/prod/".to_string()];
+
+        assert_eq!(
+            candidate_paths(&prefixes, Precedence::LastWins, "DB_HOST", None),
+            vec!["/studio/prod/DB_HOST", "/studio/DB_HOST", "/shared/DB_HOST"]
+        );
+        assert_eq!(
+            candidate_paths(&prefixes, Precedence::FirstWins, "DB_HOST", Some("db/host")),
+            vec!["/shared/db/host", "/studio/db/host", "/studio/prod/db/host"]
+        );
+        assert_eq!(
+            candidate_paths(&prefixes, Precedence::LastWins, "DB_HOST", Some("/global/db/host")),
+            vec!["/global/db/host"]
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::Instant;
+
+use crate::env_handler::MergeSummary;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    #[value(name = "json")]
+    Json,
+}
+
+/// A machine-readable description of a run. It never contains values.
+#[derive(Debug, Default, Serialize)]
+pub struct RunReport {
+    pub success: bool,
+    pub error: Option<String>,
+    pub templates: Vec<String>,
+    pub prefixes: Vec<String>,
+    pub output: String,
+    pub strategy: String,
+    pub dry_run: bool,
+    pub keys: KeyReport,
+    /// Where each key's value came from: a parameter path or `template default`.
+    pub origins: BTreeMap<String, String>,
+    /// Changes to the output file; absent when the run stopped before writing.
+    pub changes: Option<ChangeReport>,
+    pub timings_ms: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct KeyReport {
+    /// Template keys looked up in Parameter Store, after `--ignore-keys`.
+    pub requested: Vec<String>,
+    pub found: Vec<String>,
+    pub missing: Vec<String>,
+    pub ignored: Vec<String>,
+    /// Keys whose lookup failed with an error other than not found; not listed under `missing`.
+    pub errored: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ChangeReport {
+    pub updated: Vec<String>,
+    pub added: Vec<String>,
+    pub pruned: Vec<String>,
+    pub conflicts: Vec<String>,
+}
+
+impl From<&MergeSummary> for ChangeReport {
+    fn from(summary: &MergeSummary) -> Self {
+        ChangeReport {
+            updated: summary.updated.clone(),
+            added: summary.added.clone(),
+            pruned: summary.pruned.clone(),
+            conflicts: summary.conflicts.clone(),
+        }
+    }
+}
+
+impl RunReport {
+    /// Records how long a phase took since `start`.
+    pub fn time(&mut self, phase: &str, start: Instant) {
+        self.timings_ms.insert(phase.to_string(), start.elapsed().as_millis() as u64);
+    }
+
+    /// Fills in the outcome once the run is over.
+    pub fn finish(&mut self, result: &Result<()>, start: Instant) {
+        self.success = result.is_ok();
+        self.error = result.as_ref().err().map(|e| format!("{:#}", e));
+        self.time("total", start);
+    }
+
+    pub fn render(&self, format: ReportFormat) -> Result<String> {
+        match format {
+            ReportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+        }
+    }
+
+    /// Writes the report to `destination`, or stdout when there is none.
+    pub fn write(&self, format: ReportFormat, destination: Option<&str>) -> Result<()> {
+        let rendered = self.render(format)?;
+
+        match destination {
+            Some(path) => fs::write(path, rendered + "\n")
+                .with_context(|| format!("Failed to write report: {}", path)),
+            None => {
+                println!("{}", rendered);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_render_json() {
+        let mut report = RunReport {
+            templates: vec![".env.example".to_string()],
+            prefixes: vec!["/studio-dev/".to_string()],
+            output: ".env".to_string(),
+            strategy: "update".to_string(),
+            ..RunReport::default()
+        };
+        report.keys.found.push("DB_HOST".to_string());
+        report.origins.insert("DB_HOST".to_string(), "/studio-dev/DB_HOST".to_string());
+        report.changes = Some(ChangeReport::from(&MergeSummary {
+            added: vec!["DB_HOST".to_string()],
+            ..MergeSummary::default()
+        }));
+        report.finish(&Err(anyhow!("boom").context("Failed to handle .env file")), Instant::now());
+
+        let json: serde_json::Value = serde_json::from_str(&report.render(ReportFormat::Json).unwrap()).unwrap();
+
+        assert_eq!(json["success"], false);
+        assert_eq!(json["error"], "Failed to handle .env file: boom");
+        assert_eq!(json["keys"]["found"][0], "DB_HOST");
+        assert_eq!(json["origins"]["DB_HOST"], "/studio-dev/DB_HOST");
+        assert_eq!(json["changes"]["added"][0], "DB_HOST");
+        assert!(json["timings_ms"]["total"].is_u64());
+    }
+}
@@ -0,0 +1,279 @@
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::diagnostics::{Diagnostic, DiagnosticCode};
+use crate::key_grammar::KeyGrammar;
+use crate::template_loader::{Declaration, TemplateEntry, TemplateLoader};
+
+/// A structured template such as `psenv.schema.yaml`.
+///
+/// ```yaml
+/// keys:
+///   - name: DATABASE_URL
+///     description: Primary Postgres connection string
+///     type: url
+///     required: true
+///     sensitive: true
+///     path: /shared/prod/db/url
+///   - name: LOG_LEVEL
+///     type: enum(debug,info,warn)
+///     default: info
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Schema {
+    #[serde(default)]
+    keys: Vec<SchemaKey>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SchemaKey {
+    name: String,
+    description: Option<String>,
+    #[serde(rename = "type")]
+    value_type: Option<String>,
+    default: Option<ScalarValue>,
+    required: Option<bool>,
+    #[serde(default)]
+    sensitive: bool,
+    path: Option<String>,
+}
+
+/// Defaults may be written as plain YAML/JSON/TOML scalars, e.g. `default: 5432`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ScalarValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl ScalarValue {
+    fn into_string(self) -> String {
+        match self {
+            ScalarValue::String(value) => value,
+            ScalarValue::Integer(value) => value.to_string(),
+            ScalarValue::Float(value) => value.to_string(),
+            ScalarValue::Bool(value) => value.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SchemaFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl SchemaFormat {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "yaml" | "yml" => Some(SchemaFormat::Yaml),
+            "json" => Some(SchemaFormat::Json),
+            "toml" => Some(SchemaFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+pub struct SchemaLoader {
+    key_grammar: KeyGrammar,
+}
+
+impl SchemaLoader {
+    pub fn with_key_grammar(key_grammar: KeyGrammar) -> Self {
+        SchemaLoader { key_grammar }
+    }
+
+    /// Whether `path` is a structured schema file, judged by its extension.
+    pub fn handles(path: &Path) -> bool {
+        SchemaFormat::from_path(path).is_some()
+    }
+
+    fn parse(&self, content: &str, format: SchemaFormat) -> Result<Schema> {
+        let schema = match format {
+            SchemaFormat::Yaml => serde_yaml::from_str(content)?,
+            SchemaFormat::Json => serde_json::from_str(content)?,
+            SchemaFormat::Toml => toml::from_str(content)?,
+        };
+        Ok(schema)
+    }
+}
+
+impl TemplateLoader for SchemaLoader {
+    fn load(&self, path: &Path, declarations: &mut Vec<Declaration>, diagnostics: &mut Vec<Diagnostic>) -> Result<()> {
+        debug!("Loading schema file: {}", path.display());
+
+        let source = path.display().to_string();
+        let format = SchemaFormat::from_path(path)
+            .ok_or_else(|| anyhow!("Unsupported schema format: {}", source))?;
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read schema file: {}", source))?;
+        let schema = self.parse(&content, format)
+            .with_context(|| format!("Failed to parse schema file: {}", source))?;
+
+        let lines: Vec<&str> = content.lines().collect();
+
+        for key in schema.keys {
+            // Serde doesn't keep positions, so point at the first line naming the key
+            let (line, column) = locate(&lines, &key.name);
+            let mut declaration = Declaration {
+                entry: TemplateEntry {
+                    key: key.name.clone(),
+                    default: key.default.map(ScalarValue::into_string),
+                    description: key.description,
+                    source: source.clone(),
+                    line,
+                    column,
+                    commented: false,
+                    required: key.required,
+                    sensitive: key.sensitive,
+                    value_type: None,
+                    path: key.path,
+                },
+                snippet: lines.get(line.saturating_sub(1)).unwrap_or(&"").to_string(),
+            };
+
+            if !self.key_grammar.is_key(&key.name) {
+                diagnostics.push(declaration.diagnostic(
+                    DiagnosticCode::SkippedKey,
+                    format!("skipping `{}`, which does not match the key grammar", key.name),
+                ));
+                continue;
+            }
+
+            if let Some(value_type) = &key.value_type {
+                match value_type.parse() {
+                    Ok(value_type) => declaration.entry.value_type = Some(value_type),
+                    Err(e) => diagnostics.push(declaration.diagnostic(DiagnosticCode::InvalidAnnotation, e.to_string())),
+                }
+            }
+
+            declarations.push(declaration);
+        }
+
+        Ok(())
+    }
+}
+
+/// Finds the 1-based line and column where `name` first appears as a whole word.
+fn locate(lines: &[&str], name: &str) -> (usize, usize) {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+
+    for (i, line) in lines.iter().enumerate() {
+        for (offset, _) in line.match_indices(name) {
+            let before = line[..offset].chars().next_back();
+            let after = line[offset + name.len()..].chars().next();
+            if !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char) {
+                return (i + 1, line[..offset].chars().count() + 1);
+            }
+        }
+    }
+
+    (1, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value_type::ValueType;
+
+    fn load(file_name: &str, content: &str) -> (Vec<TemplateEntry>, Vec<Diagnostic>) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(file_name);
+        fs::write(&path, content).unwrap();
+
+        let mut declarations = Vec::new();
+        let mut diagnostics = Vec::new();
+        SchemaLoader::with_key_grammar(KeyGrammar::default())
+            .load(&path, &mut declarations, &mut diagnostics)
+            .unwrap();
+
+        (declarations.into_iter().map(|d| d.entry).collect(), diagnostics)
+    }
+
+    #[test]
+    fn test_load_yaml_schema() {
+        let (entries, diagnostics) = load(
+            "psenv.schema.yaml",
+            r#"
+keys:
+  - name: DATABASE_URL
+    description: Primary Postgres connection string
+    type: url
+    required: true
+    sensitive: true
+    path: /shared/prod/db/url
+  - name: DB_PORT
+    type: int
+    default: 5432
+"#,
+        );
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(entries.len(), 2);
+
+        let database_url = &entries[0];
+        assert_eq!(database_url.key, "DATABASE_URL");
+        assert_eq!(database_url.description.as_deref(), Some("Primary Postgres connection string"));
+        assert_eq!(database_url.value_type, Some(ValueType::Url));
+        assert_eq!(database_url.required, Some(true));
+        assert!(database_url.sensitive);
+        assert_eq!(database_url.path.as_deref(), Some("/shared/prod/db/url"));
+        assert_eq!((database_url.line, database_url.column), (3, 11));
+
+        let db_port = &entries[1];
+        assert_eq!(db_port.default.as_deref(), Some("5432"));
+        assert_eq!(db_port.value_type, Some(ValueType::Int));
+        assert_eq!(db_port.required, None);
+    }
+
+    #[test]
+    fn test_load_json_and_toml_schema() {
+        let (entries, _) = load(
+            "psenv.schema.json",
+            r#"{"keys": [{"name": "DEBUG", "type": "bool", "default": false}]}"#,
+        );
+        assert_eq!(entries[0].key, "DEBUG");
+        assert_eq!(entries[0].default.as_deref(), Some("false"));
+
+        let (entries, _) = load(
+            "psenv.schema.toml",
+            "[[keys]]\nname = \"LOG_LEVEL\"\ntype = \"enum(debug,info)\"\ndefault = \"info\"\n",
+        );
+        assert_eq!(entries[0].key, "LOG_LEVEL");
+        assert_eq!(entries[0].line, 2);
+        assert_eq!(
+            entries[0].value_type,
+            Some(ValueType::Enum(vec!["debug".to_string(), "info".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_load_schema_diagnostics() {
+        let (entries, diagnostics) = load(
+            "psenv.schema.yaml",
+            "keys:\n  - name: nextAuthUrl\n  - name: RATIO\n    type: float\n",
+        );
+
+        assert_eq!(entries.len(), 1);
+        let codes: Vec<DiagnosticCode> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, vec![DiagnosticCode::SkippedKey, DiagnosticCode::InvalidAnnotation]);
+    }
+
+    #[test]
+    fn test_unknown_schema_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("psenv.schema.yaml");
+        fs::write(&path, "keys:\n  - name: A\n    requried: true\n").unwrap();
+
+        let loader = SchemaLoader::with_key_grammar(KeyGrammar::default());
+        assert!(loader.load(&path, &mut Vec::new(), &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::diagnostics::{Diagnostic, DiagnosticCode};
+use crate::key_grammar::KeyGrammar;
+use crate::schema_loader::SchemaLoader;
+use crate::template_parser::TemplateParser;
+use crate::value_type::ValueType;
+
+/// A key declared in a template, together with its directives. Every
+/// template format is loaded into this model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateEntry {
+    pub key: String,
+    pub default: Option<String>,
+    pub description: Option<String>,
+    /// The template file the key was found in.
+    pub source: String,
+    pub line: usize,
+    pub column: usize,
+    pub commented: bool,
+    /// `Some(true)` for `@required`, `Some(false)` for `@optional`.
+    pub required: Option<bool>,
+    pub sensitive: bool,
+    pub value_type: Option<ValueType>,
+    pub path: Option<String>,
+}
+
+impl TemplateEntry {
+    /// Whether a missing value for this key is fatal, given the global `--require-all` flag.
+    pub fn is_required(&self, require_all: bool) -> bool {
+        self.required.unwrap_or(require_all)
+    }
+
+    pub fn location(&self) -> String {
+        format!("{}:{}:{}", self.source, self.line, self.column)
+    }
+}
+
+/// The result of parsing templates: each key once, plus every problem found on the way.
+#[derive(Debug)]
+pub struct ParsedTemplates {
+    pub entries: Vec<TemplateEntry>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A single declaration of a key, before duplicates are merged.
+pub struct Declaration {
+    pub entry: TemplateEntry,
+    /// The source line, shown in diagnostics.
+    pub snippet: String,
+}
+
+impl Declaration {
+    pub fn diagnostic(&self, code: DiagnosticCode, message: String) -> Diagnostic {
+        Diagnostic {
+            code,
+            message,
+            source: self.entry.source.clone(),
+            line: self.entry.line,
+            column: self.entry.column,
+            width: self.entry.key.chars().count(),
+            snippet: self.snippet.clone(),
+            notes: Vec::new(),
+        }
+    }
+}
+
+/// A template format that can be loaded into `TemplateEntry` values.
+pub trait TemplateLoader {
+    /// Appends every key declaration in `path`, in file order. Duplicates are
+    /// merged by the caller.
+    fn load(&self, path: &Path, declarations: &mut Vec<Declaration>, diagnostics: &mut Vec<Diagnostic>) -> Result<()>;
+}
+
+/// Loads templates with the loader matching each file's format: structured
+/// schema files by extension, `.env`-style templates otherwise.
+pub struct TemplateReader {
+    env_parser: TemplateParser,
+    schema_loader: SchemaLoader,
+}
+
+impl TemplateReader {
+    pub fn new() -> Self {
+        Self::with_key_grammar(KeyGrammar::default())
+    }
+
+    pub fn with_key_grammar(key_grammar: KeyGrammar) -> Self {
+        TemplateReader {
+            env_parser: TemplateParser::with_key_grammar(key_grammar.clone()),
+            schema_loader: SchemaLoader::with_key_grammar(key_grammar),
+        }
+    }
+
+    fn loader_for(&self, path: &Path) -> &dyn TemplateLoader {
+        if SchemaLoader::handles(path) {
+            &self.schema_loader
+        } else {
+            &self.env_parser
+        }
+    }
+
+    /// Loads one or more templates of any supported format and returns each
+    /// key once, sorted by name.
+    ///
+    /// Invalid annotations are errors; other problems are logged as warnings.
+    pub fn parse_entries(&self, template_paths: &[String]) -> Result<Vec<TemplateEntry>> {
+        let parsed = self.parse(template_paths)?;
+
+        for diagnostic in &parsed.diagnostics {
+            if diagnostic.code == DiagnosticCode::InvalidAnnotation {
+                return Err(anyhow!("Invalid annotation at {}", diagnostic));
+            }
+            warn!("{}", diagnostic);
+        }
+
+        Ok(parsed.entries)
+    }
+
+    /// Like `parse_entries`, but returns problems as diagnostics instead of logging them.
+    pub fn parse(&self, template_paths: &[String]) -> Result<ParsedTemplates> {
+        let mut declarations = Vec::new();
+        let mut diagnostics = Vec::new();
+
+        for template_path in template_paths {
+            let path = Path::new(template_path);
+            self.loader_for(path).load(path, &mut declarations, &mut diagnostics)?;
+        }
+
+        let entries = merge_declarations(declarations, &mut diagnostics);
+        diagnostics.sort_by(|a, b| (&a.source, a.line, a.column).cmp(&(&b.source, b.line, b.column)));
+
+        debug!("Parsed {} unique keys from {} template(s)", entries.len(), template_paths.len());
+
+        Ok(ParsedTemplates { entries, diagnostics })
+    }
+}
+
+impl Default for TemplateReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Deduplicates keys, keeping the first definition, and reports duplicates
+/// that disagree with each other.
+fn merge_declarations(declarations: Vec<Declaration>, diagnostics: &mut Vec<Diagnostic>) -> Vec<TemplateEntry> {
+    let mut groups: Vec<Vec<Declaration>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for declaration in declarations {
+        match index.get(&declaration.entry.key) {
+            Some(&i) => groups[i].push(declaration),
+            None => {
+                index.insert(declaration.entry.key.clone(), groups.len());
+                groups.push(vec![declaration]);
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+
+    for group in groups {
+        let commented = group.iter().find(|d| d.entry.commented);
+        let uncommented = group.iter().find(|d| !d.entry.commented);
+        if let (Some(commented), Some(uncommented)) = (commented, uncommented) {
+            let mut diagnostic = commented.diagnostic(
+                DiagnosticCode::CommentedAndUncommented,
+                format!("`{}` appears both commented and uncommented", commented.entry.key),
+            );
+            diagnostic.notes.push(format!("uncommented at {}", uncommented.entry.location()));
+            diagnostics.push(diagnostic);
+        }
+
+        for (i, declaration) in group.iter().enumerate().skip(1) {
+            let previous = group[..i].iter().find(|d| d.entry.commented == declaration.entry.commented);
+            if let Some(previous) = previous.filter(|p| p.entry.default != declaration.entry.default) {
+                let mut diagnostic = declaration.diagnostic(
+                    DiagnosticCode::DuplicateKey,
+                    format!("`{}` is defined again with a different default", declaration.entry.key),
+                );
+                diagnostic.notes.push(format!("first defined at {}", previous.entry.location()));
+                diagnostics.push(diagnostic);
+            }
+        }
+
+        let mut declarations = group.into_iter();
+        let Some(first) = declarations.next() else { continue };
+        let mut entry = first.entry;
+
+        // A later declaration only contributes the directives the first one lacks
+        for declaration in declarations {
+            for (kept, ignored) in merge_entry(&mut entry, declaration.entry.clone()) {
+                let mut diagnostic = declaration.diagnostic(
+                    DiagnosticCode::ConflictingAnnotation,
+                    format!("conflicting annotation for `{}`: {} is ignored", entry.key, ignored),
+                );
+                diagnostic.notes.push(format!("keeping {} from {}", kept, entry.location()));
+                diagnostics.push(diagnostic);
+            }
+        }
+
+        entries.push(entry);
+    }
+
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    entries
+}
+
+/// Fills in directives `existing` lacks from `other`, and returns the
+/// `(kept, ignored)` directives that disagree.
+fn merge_entry(existing: &mut TemplateEntry, other: TemplateEntry) -> Vec<(String, String)> {
+    let required = |required: Option<bool>| match required {
+        Some(true) => "@required".to_string(),
+        Some(false) => "@optional".to_string(),
+        None => String::new(),
+    };
+    let value_type = |value_type: &Option<ValueType>| value_type.as_ref().map(|t| format!("@type={}", t)).unwrap_or_default();
+    let path = |path: &Option<String>| path.as_ref().map(|p| format!("@path={}", p)).unwrap_or_default();
+
+    let conflicts = [
+        (required(existing.required), required(other.required)),
+        (value_type(&existing.value_type), value_type(&other.value_type)),
+        (path(&existing.path), path(&other.path)),
+    ];
+
+    existing.default = existing.default.take().or(other.default);
+    existing.description = existing.description.take().or(other.description);
+    existing.required = existing.required.or(other.required);
+    existing.sensitive |= other.sensitive;
+    existing.value_type = existing.value_type.take().or(other.value_type);
+    existing.path = existing.path.take().or(other.path);
+
+    conflicts
+        .into_iter()
+        .filter(|(kept, ignored)| !kept.is_empty() && !ignored.is_empty() && kept != ignored)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_parse_multiple_templates_and_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("shared")).unwrap();
+        fs::create_dir(dir.path().join("svc")).unwrap();
+
+        fs::write(
+            dir.path().join("shared/.env.example"),
+            "# @required\nDATABASE_URL=\nLOG_LEVEL=info\n",
+        ).unwrap();
+        fs::write(
+            dir.path().join("svc/.env.example"),
+            "# @include ../shared/.env.example\n\n# @optional\nDATABASE_URL=\nSVC_PORT=8080\n",
+        ).unwrap();
+        fs::write(dir.path().join("extra.env"), "EXTRA_KEY=\nLOG_LEVEL=debug\n").unwrap();
+
+        let paths = vec![
+            dir.path().join("svc/.env.example").to_str().unwrap().to_string(),
+            dir.path().join("extra.env").to_str().unwrap().to_string(),
+        ];
+        let entries = TemplateReader::new().parse_entries(&paths).unwrap();
+
+        let keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["DATABASE_URL", "EXTRA_KEY", "LOG_LEVEL", "SVC_PORT"]);
+
+        // The first definition wins, and keeps track of where it came from
+        let database_url = &entries[0];
+        assert_eq!(database_url.required, Some(true));
+        assert!(database_url.source.ends_with("svc/../shared/.env.example"));
+        assert_eq!(database_url.line, 2);
+
+        let log_level = &entries[2];
+        assert_eq!(log_level.default.as_deref(), Some("info"));
+    }
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let template_content = "DB_HOST=localhost\n  export DB_HOST=other\n# DB_PORT=5432\nDB_PORT=5432\nJUST A LINE\n# @sensitive\n";
+
+        let temp_file = NamedTempFile::new().unwrap();
+        fs::write(temp_file.path(), template_content).unwrap();
+
+        let parsed = TemplateReader::new().parse(&[temp_file.path().to_str().unwrap().to_string()]).unwrap();
+
+        let found: Vec<(DiagnosticCode, usize, usize)> = parsed.diagnostics.iter()
+            .map(|d| (d.code, d.line, d.column))
+            .collect();
+        assert_eq!(found, vec![
+            (DiagnosticCode::DuplicateKey, 2, 10),
+            (DiagnosticCode::CommentedAndUncommented, 3, 3),
+            (DiagnosticCode::MalformedLine, 5, 1),
+            (DiagnosticCode::DanglingAnnotation, 6, 3),
+        ]);
+
+        let duplicate = &parsed.diagnostics[0];
+        assert_eq!(duplicate.width, 7);
+        assert!(duplicate.notes[0].ends_with(":1:1"));
+
+        // Duplicates are still merged into a single entry
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.entries[0].default.as_deref(), Some("localhost"));
+    }
+
+    #[test]
+    fn test_parse_mixed_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("psenv.schema.yaml"),
+            "keys:\n  - name: DATABASE_URL\n    description: Primary database\n    required: true\n",
+        ).unwrap();
+        fs::write(dir.path().join(".env.example"), "# @optional\nDATABASE_URL=\nLOG_LEVEL=info\n").unwrap();
+
+        let paths = vec![
+            dir.path().join("psenv.schema.yaml").to_str().unwrap().to_string(),
+            dir.path().join(".env.example").to_str().unwrap().to_string(),
+        ];
+        let parsed = TemplateReader::new().parse(&paths).unwrap();
+
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.entries[0].description.as_deref(), Some("Primary database"));
+        assert_eq!(parsed.entries[0].required, Some(true));
+        assert_eq!(parsed.diagnostics.len(), 1);
+        assert_eq!(parsed.diagnostics[0].code, DiagnosticCode::ConflictingAnnotation);
+    }
+}
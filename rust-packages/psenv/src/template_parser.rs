@@ -1,53 +1,260 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use log::debug;
 use regex::Regex;
-use std::collections::HashSet;
 use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::{Diagnostic, DiagnosticCode};
+use crate::key_grammar::KeyGrammar;
+use crate::template_loader::{Declaration, TemplateEntry, TemplateLoader};
+use crate::value_type::ValueType;
+
+#[derive(Debug, Default)]
+struct Annotations {
+    required: Option<bool>,
+    sensitive: bool,
+    value_type: Option<ValueType>,
+    path: Option<String>,
+}
+
+/// A line being parsed, used to point diagnostics at part of it.
+struct SourceLine<'a> {
+    source: &'a str,
+    number: usize,
+    text: &'a str,
+}
+
+impl SourceLine<'_> {
+    /// `part` must be a slice of the line's text.
+    fn diagnostic(&self, code: DiagnosticCode, message: String, part: &str) -> Diagnostic {
+        Diagnostic {
+            code,
+            message,
+            source: self.source.to_string(),
+            line: self.number,
+            column: self.column_of(part),
+            width: part.chars().count(),
+            snippet: self.text.to_string(),
+            notes: Vec::new(),
+        }
+    }
+
+    fn column_of(&self, part: &str) -> usize {
+        let offset = (part.as_ptr() as usize).saturating_sub(self.text.as_ptr() as usize);
+        self.text.get(..offset).map_or(0, |prefix| prefix.chars().count()) + 1
+    }
+}
 
 pub struct TemplateParser {
-    env_key_regex: Regex,
+    key_grammar: KeyGrammar,
+    annotation_regex: Regex,
 }
 
 impl TemplateParser {
     pub fn new() -> Self {
-        // Regex to match environment variable keys in .env files
-        // Matches lines like: KEY=value, KEY= (empty value), # KEY=value (commented)
-        let env_key_regex = Regex::new(r"^#?\s*([A-Z_][A-Z0-9_]*)\s*=").unwrap();
+        Self::with_key_grammar(KeyGrammar::default())
+    }
 
-        TemplateParser { env_key_regex }
+    pub fn with_key_grammar(key_grammar: KeyGrammar) -> Self {
+        // Matches directives like: @required, @type=enum(a,b), @path=/shared/DB_URL
+        let annotation_regex = Regex::new(r"@([a-z]+)(?:=(enum\([^)]*\)|\S+))?").unwrap();
+
+        TemplateParser { key_grammar, annotation_regex }
     }
 
-    pub fn parse_template(&self, template_path: &str) -> Result<Vec<String>> {
-        debug!("Parsing template file: {}", template_path);
+    fn parse_file(
+        &self,
+        template_path: &Path,
+        stack: &mut Vec<PathBuf>,
+        declarations: &mut Vec<Declaration>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<()> {
+        debug!("Parsing template file: {}", template_path.display());
 
+        let source = template_path.display().to_string();
         let content = fs::read_to_string(template_path)
-            .with_context(|| format!("Failed to read template file: {}", template_path))?;
+            .with_context(|| format!("Failed to read template file: {}", source))?;
+
+        let canonical = template_path.canonicalize()
+            .with_context(|| format!("Failed to resolve template file: {}", source))?;
+        if stack.contains(&canonical) {
+            let chain: Vec<String> = stack.iter().chain([&canonical]).map(|p| p.display().to_string()).collect();
+            return Err(anyhow!("Circular include: {}", chain.join(" -> ")));
+        }
+        stack.push(canonical);
 
-        let mut keys = HashSet::new();
+        let mut pending = Annotations::default();
+        let mut pending_start: Option<Diagnostic> = None;
+        // Plain comment lines directly above a key describe it
+        let mut comments: Vec<&str> = Vec::new();
 
         for (line_num, line) in content.lines().enumerate() {
             let trimmed = line.trim();
+            let current = SourceLine { source: &source, number: line_num + 1, text: line };
 
-            // Skip empty lines and comments that don't contain env vars
-            if trimmed.is_empty() || (trimmed.starts_with('#') && !trimmed.contains('=')) {
+            if trimmed.is_empty() {
+                comments.clear();
+                if let Some(diagnostic) = pending_start.take() {
+                    diagnostics.push(diagnostic);
+                    pending = Annotations::default();
+                }
                 continue;
             }
 
-            if let Some(captures) = self.env_key_regex.captures(trimmed) {
-                if let Some(key_match) = captures.get(1) {
-                    let key = key_match.as_str().to_string();
-                    debug!("Found key '{}' on line {}", key, line_num + 1);
-                    keys.insert(key);
+            if let Some(comment) = trimmed.strip_prefix('#') {
+                let comment = comment.trim_start();
+
+                if let Some(include) = comment.strip_prefix("@include ") {
+                    let include_path = template_path.parent().unwrap_or(Path::new("")).join(include.trim());
+                    debug!("{}:{}: including {}", source, line_num + 1, include_path.display());
+                    self.parse_file(&include_path, stack, declarations, diagnostics)
+                        .with_context(|| format!("Failed to include template at {}:{}", source, line_num + 1))?;
+                    continue;
+                }
+
+                if comment.starts_with('@') {
+                    self.parse_annotations(comment, &mut pending, &current, diagnostics);
+                    if pending_start.is_none() {
+                        pending_start = Some(current.diagnostic(
+                            DiagnosticCode::DanglingAnnotation,
+                            "annotations are not followed by a key and are ignored".to_string(),
+                            comment,
+                        ));
+                    }
+                    continue;
+                }
+            }
+
+            // Matches lines like: KEY=value, KEY= (empty value), # KEY=value (commented)
+            let Some(assignment) = self.key_grammar.parse_assignment(trimmed) else {
+                // Comments are free-form, but anything else should be an assignment
+                if let Some(comment) = trimmed.strip_prefix('#') {
+                    if !comment.trim().is_empty() {
+                        comments.push(comment.trim());
+                    }
+                } else {
+                    diagnostics.push(current.diagnostic(
+                        DiagnosticCode::MalformedLine,
+                        "expected a KEY=value assignment".to_string(),
+                        trimmed,
+                    ));
+                }
+                continue;
+            };
+
+            if !self.key_grammar.is_key(assignment.name) {
+                // Commented lines are often prose, so only real assignments are worth a warning
+                if !assignment.commented {
+                    diagnostics.push(current.diagnostic(
+                        DiagnosticCode::SkippedKey,
+                        format!("skipping `{}`, which does not match the key grammar", assignment.name),
+                        assignment.name,
+                    ));
                 }
+                continue;
+            }
+
+            debug!("Found key '{}' on line {}", assignment.name, line_num + 1);
+
+            if let Some(value) = unterminated_quote(assignment.value) {
+                diagnostics.push(current.diagnostic(
+                    DiagnosticCode::MalformedLine,
+                    format!("unterminated quote in the default value of `{}`", assignment.name),
+                    value,
+                ));
+            }
+
+            let annotations = std::mem::take(&mut pending);
+            pending_start = None;
+            let description = Some(comments.join(" ")).filter(|d| !d.is_empty());
+            comments.clear();
+
+            declarations.push(Declaration {
+                entry: TemplateEntry {
+                    key: assignment.name.to_string(),
+                    default: parse_default(assignment.value),
+                    description,
+                    source: source.clone(),
+                    line: line_num + 1,
+                    column: current.column_of(assignment.name),
+                    commented: assignment.commented,
+                    required: annotations.required,
+                    sensitive: annotations.sensitive,
+                    value_type: annotations.value_type,
+                    path: annotations.path,
+                },
+                snippet: line.to_string(),
+            });
+        }
+
+        if let Some(diagnostic) = pending_start {
+            diagnostics.push(diagnostic);
+        }
+
+        stack.pop();
+        Ok(())
+    }
+
+    fn parse_annotations(
+        &self,
+        comment: &str,
+        annotations: &mut Annotations,
+        line: &SourceLine,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        for captures in self.annotation_regex.captures_iter(comment) {
+            let directive = captures.get(0).unwrap().as_str();
+            let name = &captures[1];
+            let value = captures.get(2).map(|m| m.as_str());
+
+            match (name, value) {
+                ("required", None) => annotations.required = Some(true),
+                ("optional", None) => annotations.required = Some(false),
+                ("sensitive", None) => annotations.sensitive = true,
+                ("type", Some(value)) => match value.parse() {
+                    Ok(value_type) => annotations.value_type = Some(value_type),
+                    Err(e) => diagnostics.push(line.diagnostic(DiagnosticCode::InvalidAnnotation, e.to_string(), directive)),
+                },
+                ("path", Some(value)) => annotations.path = Some(value.to_string()),
+                _ => diagnostics.push(line.diagnostic(
+                    DiagnosticCode::UnknownAnnotation,
+                    format!("ignoring unknown template annotation `{}`", directive),
+                    directive,
+                )),
             }
         }
+    }
+}
+
+fn parse_default(raw: &str) -> Option<String> {
+    let value = raw.trim();
+    let unquoted = ['"', '\'']
+        .iter()
+        .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+        .unwrap_or(value);
 
-        let mut result: Vec<String> = keys.into_iter().collect();
-        result.sort();
+    if unquoted.is_empty() {
+        None
+    } else {
+        Some(unquoted.to_string())
+    }
+}
+
+/// Returns the value if it opens a quote that it never closes.
+fn unterminated_quote(raw: &str) -> Option<&str> {
+    let value = raw.trim();
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
 
-        debug!("Parsed {} unique keys from template", result.len());
+    if value.len() > 1 && value.ends_with(quote) {
+        None
+    } else {
+        Some(value)
+    }
+}
 
-        Ok(result)
+impl TemplateLoader for TemplateParser {
+    fn load(&self, path: &Path, declarations: &mut Vec<Declaration>, diagnostics: &mut Vec<Diagnostic>) -> Result<()> {
+        self.parse_file(path, &mut Vec::new(), declarations, diagnostics)
     }
 }
 
@@ -60,9 +267,24 @@ impl Default for TemplateParser {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::key_grammar::KeyGrammarKind;
+    use crate::template_loader::TemplateReader;
     use std::fs;
     use tempfile::NamedTempFile;
 
+    fn load_entries(parser: &TemplateParser, path: &str) -> Vec<TemplateEntry> {
+        let mut declarations = Vec::new();
+        parser.load(Path::new(path), &mut declarations, &mut Vec::new()).unwrap();
+        declarations.into_iter().map(|declaration| declaration.entry).collect()
+    }
+
+    fn parse_keys(parser: &TemplateParser, path: &str) -> Vec<String> {
+        let mut keys: Vec<String> = load_entries(parser, path).into_iter().map(|entry| entry.key).collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
     #[test]
     fn test_parse_template() {
         let parser = TemplateParser::new();
@@ -86,7 +308,7 @@ ANOTHER_KEY=value
         let temp_file = NamedTempFile::new().unwrap();
         fs::write(temp_file.path(), template_content).unwrap();
 
-        let keys = parser.parse_template(temp_file.path().to_str().unwrap()).unwrap();
+        let keys = parse_keys(&parser, temp_file.path().to_str().unwrap());
 
         let expected_keys = vec![
             "ANOTHER_KEY",
@@ -113,10 +335,116 @@ DB_HOST=localhost
         let temp_file = NamedTempFile::new().unwrap();
         fs::write(temp_file.path(), template_content).unwrap();
 
-        let keys = parser.parse_template(temp_file.path().to_str().unwrap()).unwrap();
+        let keys = parse_keys(&parser, temp_file.path().to_str().unwrap());
 
         let expected_keys = vec!["API_KEY", "DB_HOST", "DB_PORT"];
 
         assert_eq!(keys, expected_keys);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_annotations() {
+        let parser = TemplateParser::new();
+
+        let template_content = r#"
+# Primary database
+# @required @sensitive
+# @type=url
+DATABASE_URL=
+
+# @optional @type=enum(debug, info, warn)
+LOG_LEVEL=info
+
+# @path=/shared/prod/db/port
+# @type=int
+DB_PORT="5432"
+
+PLAIN_KEY=
+"#;
+
+        let temp_file = NamedTempFile::new().unwrap();
+        fs::write(temp_file.path(), template_content).unwrap();
+
+        let entries = load_entries(&parser, temp_file.path().to_str().unwrap());
+        let entry = |key: &str| entries.iter().find(|e| e.key == key).unwrap();
+
+        let database_url = entry("DATABASE_URL");
+        assert_eq!(database_url.required, Some(true));
+        assert!(database_url.sensitive);
+        assert_eq!(database_url.value_type, Some(ValueType::Url));
+        assert_eq!(database_url.default, None);
+        assert_eq!(database_url.line, 5);
+        assert_eq!(database_url.description.as_deref(), Some("Primary database"));
+
+        let log_level = entry("LOG_LEVEL");
+        assert_eq!(log_level.required, Some(false));
+        assert_eq!(
+            log_level.value_type,
+            Some(ValueType::Enum(vec!["debug".to_string(), "info".to_string(), "warn".to_string()]))
+        );
+        assert_eq!(log_level.default.as_deref(), Some("info"));
+
+        let db_port = entry("DB_PORT");
+        assert_eq!(db_port.path.as_deref(), Some("/shared/prod/db/port"));
+        assert_eq!(db_port.value_type, Some(ValueType::Int));
+        assert_eq!(db_port.default.as_deref(), Some("5432"));
+
+        let plain = entry("PLAIN_KEY");
+        assert_eq!(plain.description, None);
+        assert_eq!(plain.required, None);
+        assert!(!plain.sensitive);
+        assert!(plain.is_required(true));
+        assert!(!log_level.is_required(true));
+        assert!(database_url.is_required(false));
+    }
+
+    #[test]
+    fn test_parse_with_key_grammar() {
+        let template_content = "DB_HOST=localhost\nnextAuthUrl=\nvite_public_x=1\nmy-key=\n";
+
+        let temp_file = NamedTempFile::new().unwrap();
+        fs::write(temp_file.path(), template_content).unwrap();
+        let path = temp_file.path().to_str().unwrap();
+
+        assert_eq!(parse_keys(&TemplateParser::new(), path), vec!["DB_HOST"]);
+
+        let parser = TemplateParser::with_key_grammar(KeyGrammar::new(KeyGrammarKind::Identifier));
+        assert_eq!(parse_keys(&parser, path), vec!["DB_HOST", "nextAuthUrl", "vite_public_x"]);
+
+        let parser = TemplateParser::with_key_grammar(KeyGrammar::custom(r"[a-z-]+").unwrap());
+        assert_eq!(parse_keys(&parser, path), vec!["my-key"]);
+    }
+
+    #[test]
+    fn test_parse_export_lines() {
+        let template_content = "export DB_HOST=localhost\n# export DB_PORT=5432\nexport API_KEY=\n";
+
+        let temp_file = NamedTempFile::new().unwrap();
+        fs::write(temp_file.path(), template_content).unwrap();
+
+        let keys = parse_keys(&TemplateParser::new(), temp_file.path().to_str().unwrap());
+        assert_eq!(keys, vec!["API_KEY", "DB_HOST", "DB_PORT"]);
+    }
+
+    #[test]
+    fn test_parse_invalid_annotation() {
+        let temp_file = NamedTempFile::new().unwrap();
+        fs::write(temp_file.path(), "# @type=float\nRATIO=\n").unwrap();
+
+        let reader = TemplateReader::new();
+        assert!(reader.parse_entries(&[temp_file.path().to_str().unwrap().to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_circular_include() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.env"), "# @include b.env\nA=\n").unwrap();
+        fs::write(dir.path().join("b.env"), "# @include a.env\nB=\n").unwrap();
+
+        let err = TemplateParser::new()
+            .load(&dir.path().join("a.env"), &mut Vec::new(), &mut Vec::new())
+            .unwrap_err();
+
+        assert!(format!("{:#}", err).contains("Circular include"));
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Value type declared with a `# @type=...` template annotation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueType {
+    Int,
+    Url,
+    Bool,
+    Enum(Vec<String>),
+}
+
+impl ValueType {
+    pub fn validate(&self, value: &str) -> std::result::Result<(), String> {
+        match self {
+            ValueType::Int => value
+                .trim()
+                .parse::<i64>()
+                .map(|_| ())
+                .map_err(|_| "expected an integer".to_string()),
+            ValueType::Url => {
+                if is_url(value) {
+                    Ok(())
+                } else {
+                    Err("expected a URL (scheme://host...)".to_string())
+                }
+            }
+            ValueType::Bool => match value.trim().to_lowercase().as_str() {
+                "true" | "false" | "1" | "0" | "yes" | "no" | "on" | "off" => Ok(()),
+                _ => Err("expected a boolean (true/false, 1/0, yes/no, on/off)".to_string()),
+            },
+            ValueType::Enum(variants) => {
+                if variants.iter().any(|v| v == value) {
+                    Ok(())
+                } else {
+                    Err(format!("expected one of: {}", variants.join(", ")))
+                }
+            }
+        }
+    }
+}
+
+fn is_url(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return false;
+    };
+
+    let scheme_ok = scheme
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+
+    scheme_ok && !rest.is_empty() && !rest.starts_with('/') && !value.chars().any(char::is_whitespace)
+}
+
+impl FromStr for ValueType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s {
+            "int" | "integer" => Ok(ValueType::Int),
+            "url" => Ok(ValueType::Url),
+            "bool" | "boolean" => Ok(ValueType::Bool),
+            _ => {
+                let variants = s
+                    .strip_prefix("enum(")
+                    .and_then(|rest| rest.strip_suffix(')'))
+                    .ok_or_else(|| anyhow!("Unknown value type: {}", s))?;
+
+                let variants: Vec<String> = variants
+                    .split(',')
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect();
+
+                if variants.is_empty() {
+                    return Err(anyhow!("Enum type must list at least one value: {}", s));
+                }
+
+                Ok(ValueType::Enum(variants))
+            }
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueType::Int => write!(f, "int"),
+            ValueType::Url => write!(f, "url"),
+            ValueType::Bool => write!(f, "bool"),
+            ValueType::Enum(variants) => write!(f, "enum({})", variants.join(",")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_value_type() {
+        assert_eq!("int".parse::<ValueType>().unwrap(), ValueType::Int);
+        assert_eq!("url".parse::<ValueType>().unwrap(), ValueType::Url);
+        assert_eq!("bool".parse::<ValueType>().unwrap(), ValueType::Bool);
+        assert_eq!(
+            "enum(dev, prod)".parse::<ValueType>().unwrap(),
+            ValueType::Enum(vec!["dev".to_string(), "prod".to_string()])
+        );
+
+        assert!("float".parse::<ValueType>().is_err());
+        assert!("enum()".parse::<ValueType>().is_err());
+    }
+
+    #[test]
+    fn test_validate_value() {
+        assert!(ValueType::Int.validate("5432").is_ok());
+        assert!(ValueType::Int.validate("abc").is_err());
+
+        assert!(ValueType::Url.validate("postgres://user@db:5432/app").is_ok());
+        assert!(ValueType::Url.validate("https://example.com").is_ok());
+        assert!(ValueType::Url.validate("example.com").is_err());
+        assert!(ValueType::Url.validate("http://").is_err());
+
+        assert!(ValueType::Bool.validate("TRUE").is_ok());
+        assert!(ValueType::Bool.validate("maybe").is_err());
+
+        let env = ValueType::Enum(vec!["dev".to_string(), "prod".to_string()]);
+        assert!(env.validate("prod").is_ok());
+        assert!(env.validate("staging").is_err());
+    }
+}